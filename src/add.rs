@@ -0,0 +1,180 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use anyhow::Context;
+use log::info;
+use structopt::StructOpt;
+use text_placeholder::Template;
+
+use crate::{cfg, config_dir, create_file_with_content, error::XcppError, project::{self, Layout, Manifest}};
+
+/// 项目中覆盖`cfg::ITEMS`的模板目录
+const TEMPLATE_DIR: &str = ".xcpp/templates";
+
+#[derive(StructOpt)]
+pub(crate) enum Item {
+    /// Add a class with a header and a matching source file.
+    Class {
+        /// The class name.
+        name: String,
+        /// Put the class into this namespace, e.g. `geo` or `geo::detail`.
+        #[structopt(long)]
+        namespace: Option<String>,
+    },
+    /// Add a header file, `util` creates `util.h`.
+    Header {
+        /// The header name, may contain `/` to place it in a subdirectory.
+        name: String,
+        /// Put the header's contents into this namespace.
+        #[structopt(long)]
+        namespace: Option<String>,
+    },
+    /// Add a test source in `tests/`, `foo` creates `tests/foo_test.cpp` for the project's test framework.
+    Test {
+        /// The test name.
+        name: String,
+    },
+}
+
+/// 在当前目录的项目中按布局生成`item`对应的文件，已存在的文件不会被覆盖
+pub(crate) fn add(item: Item) -> anyhow::Result<()> {
+    let dir = std::env::current_dir().context("Failed to get current directory")?;
+    let project = dir.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid project directory `{}`", dir.display()))?;
+    let manifest = Manifest::load(&dir)?.unwrap_or_default();
+    let layout = manifest.layout;
+
+    // (模板名, 生成的文件)
+    let (files, name, namespace): (Vec<(&str, PathBuf)>, _, _) = match &item {
+        Item::Class { name, namespace } => {
+            if !is_identifier(name) {
+                return Err(XcppError::Config(format!("Invalid class name `{name}`")).into());
+            }
+            let files = vec![
+                ("class.h", header_path(&dir, layout, project, name)),
+                ("class.cpp", dir.join(layout.source_dir()).join(format!("{name}.cpp"))),
+            ];
+            (files, name, namespace.as_deref())
+        }
+        Item::Header { name, namespace } => {
+            if !is_path(name) {
+                return Err(XcppError::Config(format!("Invalid header name `{name}`")).into());
+            }
+            (vec![("header.h", header_path(&dir, layout, project, name))], name, namespace.as_deref())
+        }
+        Item::Test { name } => {
+            if !is_path(name) {
+                return Err(XcppError::Config(format!("Invalid test name `{name}`")).into());
+            }
+            // 没有测试框架时`tests/`中的文件不会参与编译
+            let Some(framework) = manifest.test_framework else {
+                return Err(XcppError::Config("The project has no test framework, create it with `xcpp new --test-framework doctest` (or `catch2`, `gtest`) to add tests".to_owned()).into());
+            };
+            (vec![(framework.item(), dir.join("tests").join(format!("{name}_test.cpp")))], name, None)
+        }
+    };
+    if let Some(namespace) = namespace {
+        if !namespace.split("::").all(is_identifier) {
+            return Err(XcppError::Config(format!("Invalid namespace `{namespace}`")).into());
+        }
+    }
+    if let Some((_, path)) = files.iter().find(|(_, path)| path.exists()) {
+        return Err(XcppError::Config(format!("`{}` already exists", path.display())).into());
+    }
+
+    // 源文件通过`-I`目录包含`src`布局下的头文件
+    let include = match layout {
+        Layout::Flat => format!("\"{name}.h\""),
+        Layout::Src => format!("<{project}/{name}.h>"),
+    };
+    let (namespace_begin, namespace_end) = match namespace {
+        Some(namespace) => namespace_blocks(namespace, &manifest.std),
+        None => (String::new(), String::new()),
+    };
+    let short_name = name.rsplit('/').next().unwrap_or(name);
+    let suite = project::identifier(short_name);
+    let mut table = HashMap::new();
+    table.insert("project", project);
+    table.insert("name", short_name);
+    table.insert("suite", suite.as_str());
+    table.insert("include", include.as_str());
+    table.insert("namespace_begin", namespace_begin.as_str());
+    table.insert("namespace_end", namespace_end.as_str());
+
+    for (template, path) in files {
+        let content = load_template(&dir, template)?;
+        create_file_with_content(&path, &Template::new(&content).fill_with_hashmap(&table))?;
+        info!("Added `{}`", path.strip_prefix(&dir).unwrap_or(&path).display());
+    }
+    Ok(())
+}
+
+/// 命名空间的开头与结尾，`a::b`形式的嵌套命名空间需要C++17，更早的std改为逐层嵌套
+fn namespace_blocks(namespace: &str, std: &str) -> (String, String) {
+    if !matches!(std, "c++98" | "c++03" | "c++11" | "c++14") {
+        return (format!("\nnamespace {namespace} {{\n"), format!("\n}} // namespace {namespace}\n"));
+    }
+    let parts: Vec<_> = namespace.split("::").collect();
+    let begin: Vec<_> = parts.iter().map(|part| format!("namespace {part} {{")).collect();
+    let end: Vec<_> = parts.iter().rev().map(|part| format!("}} // namespace {part}")).collect();
+    (format!("\n{}\n", begin.join(" ")), format!("\n{}\n", end.join("\n")))
+}
+
+/// `src`布局的头文件位于`include/<project>/`，否则与源文件放在一起
+fn header_path(dir: &Path, layout: Layout, project: &str, name: &str) -> PathBuf {
+    match layout {
+        Layout::Flat => dir.join(format!("{name}.h")),
+        Layout::Src => dir.join("include").join(project).join(format!("{name}.h")),
+    }
+}
+
+/// 依次查找项目下的`.xcpp/templates/<name>`、全局配置目录下的`templates/<name>`，都不存在时使用内置模板
+pub(crate) fn load_template(dir: &Path, name: &str) -> anyhow::Result<String> {
+    let global = config_dir()?.join("templates").join(name);
+    for path in [dir.join(TEMPLATE_DIR).join(name), global] {
+        if path.is_file() {
+            info!("Using template `{}`", path.display());
+            return std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read `{}`", path.display()));
+        }
+    }
+    // 模板名都来自`cfg::ITEMS`
+    let item = cfg::ITEMS.iter().find(|item| item.path == name).unwrap();
+    Ok(item.content.to_owned())
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// 由`/`分隔的若干段文件名，不允许`..`等跳出项目目录
+fn is_path(name: &str) -> bool {
+    name.split('/').all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_test() {
+        assert!(is_identifier("Foo_2"));
+        assert!(!is_identifier("2Foo"));
+        assert!(!is_identifier("foo-bar"));
+        assert!(is_path("util/strings"));
+        assert!(!is_path("../util"));
+        assert!(!is_path("util/"));
+    }
+
+    #[test]
+    fn namespace_test() {
+        assert_eq!(namespace_blocks("geo::detail", "c++17").0, "\nnamespace geo::detail {\n");
+        assert_eq!(
+            namespace_blocks("geo::detail", "c++11"),
+            ("\nnamespace geo { namespace detail {\n".to_owned(), "\n} // namespace detail\n} // namespace geo\n".to_owned())
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::{io::Write, path::{Path, PathBuf}, process::{Command, Stdio}};
+
+use anyhow::{bail, Context};
+use log::info;
+
+use crate::{cfg::{Profile, PROFILES}, error::XcppError, flags::Flags, framework::VENDOR_DIR, judge::INTERACTOR, message, pch, plan::Output, project::{Kind, Layout, Manifest}};
+
+/// 默认的编译参数，与`C/C++: -O2`任务保持一致
+pub(crate) fn profile() -> &'static Profile {
+    find_profile("O2")
+}
+
+/// 经过`possible_values`校验，一定能找到
+pub(crate) fn find_profile(name: &str) -> &'static Profile {
+    PROFILES.iter().find(|profile| profile.name == name).unwrap()
+}
+
+/// 工具链是否支持`profile`，sanitizer需要对应的运行时库，通过编译链接一个空程序检测
+pub(crate) fn supports(toolchain: &Path, profile: &Profile) -> bool {
+    if !profile.is_sanitizer() {
+        return true;
+    }
+    let output = probe_output(profile);
+    let child = probe(toolchain, profile, &output)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let supported = child.is_ok_and(|mut child| {
+        let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(b"int main() { return 0; }\n").is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    });
+    let _ = std::fs::remove_file(&output);
+    supported
+}
+
+fn probe_output(profile: &Profile) -> PathBuf {
+    std::env::temp_dir().join(format!("xcpp-probe-{}-{}{}", std::process::id(), profile.name, std::env::consts::EXE_SUFFIX))
+}
+
+/// 检测`profile`的命令，空程序从stdin读入
+fn probe(toolchain: &Path, profile: &Profile, output: &Path) -> Command {
+    let mut cmd = Command::new(tool(toolchain, "g++"));
+    cmd.args(["-x", "c++", "-"])
+        .args(profile.flags)
+        .arg("-o")
+        .arg(output);
+    cmd
+}
+
+/// 生成项目时使用的编译参数，`--dry-run`时只在计划中列出检测sanitizer的命令，并假定都支持
+pub(crate) fn planned_profiles(output: &mut Output, dir: &Path, toolchain: &Path) -> Vec<&'static Profile> {
+    let probes = PROFILES.iter()
+        .filter(|profile| profile.is_sanitizer())
+        .map(|profile| format!("{:?}", probe(toolchain, profile, &probe_output(profile))));
+    if output.run(dir, probes) {
+        supported_profiles(toolchain)
+    } else {
+        PROFILES.iter().collect()
+    }
+}
+
+/// 工具链支持的编译参数，跳过的sanitizer会给出提示
+pub(crate) fn supported_profiles(toolchain: &Path) -> Vec<&'static Profile> {
+    PROFILES.iter()
+        .filter(|profile| {
+            let supported = supports(toolchain, profile);
+            if !supported {
+                message::warning(&format!(
+                    "Skipping profile `{}`: `{}` doesn't support `{}`",
+                    profile.name,
+                    tool(toolchain, "g++").display(),
+                    profile.flags.iter().filter(|flag| flag.starts_with("-fsanitize=")).copied().collect::<Vec<_>>().join(" ")
+                ));
+            }
+            supported
+        })
+        .collect()
+}
+
+/// 工具链中可执行文件的路径，如`g++`在Windows下为`g++.exe`
+pub(crate) fn tool(toolchain: &Path, name: &str) -> PathBuf {
+    toolchain.join(format!("{name}{}", std::env::consts::EXE_SUFFIX))
+}
+
+/// 参与编译的源文件，不包括相对`dir`的交互器`interactor`，与makefile中的`SRC`一致。
+/// `flat`布局递归查找项目根目录，跳过`target/`与`tests/`，`src`布局递归查找`src/`，库项目还包括链接它的`example/`
+pub(crate) fn sources(dir: &Path, layout: Layout, kind: Kind, interactor: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let excluded: Vec<_> = [interactor, Path::new("target"), Path::new("tests")].iter()
+        .filter_map(|path| dir.join(path).canonicalize().ok())
+        .collect();
+    let mut sources = Vec::new();
+    match layout {
+        Layout::Flat => collect_sources(dir, &excluded, &mut sources)?,
+        Layout::Src => {
+            let source_dir = dir.join(layout.source_dir());
+            // header-only的库可以没有`src/`
+            if source_dir.exists() {
+                collect_sources(&source_dir, &excluded, &mut sources)?;
+            }
+        }
+    }
+    if kind.is_library() {
+        collect_sources(&dir.join("example"), &excluded, &mut sources)?;
+    }
+    sources.sort();
+    Ok(sources)
+}
+
+/// 递归查找`dir`中的.cpp，跳过`excluded`中规范化后的文件与目录，以及make的通配符同样看不到的隐藏目录
+fn collect_sources(dir: &Path, excluded: &[PathBuf], sources: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory `{}`", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.canonicalize().is_ok_and(|path| excluded.contains(&path)) {
+            continue;
+        }
+        if path.is_dir() {
+            if !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+                collect_sources(&path, excluded, sources)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "cpp") {
+            sources.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 使用工具链中的g++按`profile`与项目追加的参数编译`sources`，生成`output`，`args`位于源文件之后，如`-l`
+pub(crate) fn compile(toolchain: &Path, std: &str, profile: &Profile, manifest: &Manifest, sources: &[PathBuf], args: &[&str], output: &Path) -> anyhow::Result<()> {
+    if sources.is_empty() {
+        bail!("No source files to compile for `{}`", output.display());
+    }
+    if let Some(parent) = output.parent() {
+        crate::mkdir(parent)?;
+    }
+    let gpp = tool(toolchain, "g++");
+    info!("Compiling `{}` with `{}`", output.display(), gpp.display());
+    let mut cmd = Command::new(&gpp);
+    cmd.arg("-fdiagnostics-color=always")
+        .arg(format!("-std={std}"))
+        .args(manifest.flags.compile(profile))
+        .arg(format!("-I{}", pch::DIR))
+        .args(manifest.layout.include_dirs().iter().map(|dir| format!("-I{dir}")))
+        .args(manifest.flags.includes(""))
+        .args(sources)
+        .args(args)
+        .args(manifest.flags.links())
+        .arg("-o")
+        .arg(output);
+    message::command_run(&cmd);
+    let status = cmd.status()
+        .map_err(|e| XcppError::Toolchain(format!("Failed to evaluate `{}`: {e}", gpp.display())))?;
+    if !status.success() {
+        bail!("Failed to compile `{}`", output.display());
+    }
+    Ok(())
+}
+
+/// 按`profile`与`flags`编译当前目录下的项目，`tests`为真时改为编译并运行单元测试
+pub(crate) fn build(std: &str, toolchain: &Path, flags: &Flags, profile: &Profile, tests: bool) -> anyhow::Result<()> {
+    if !supports(toolchain, profile) {
+        return Err(XcppError::Toolchain(format!("`{}` doesn't support the `{}` profile", tool(toolchain, "g++").display(), profile.name)).into());
+    }
+    let dir = std::env::current_dir().context("Failed to get current directory")?;
+    let name = dir.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid project directory `{}`", dir.display()))?;
+    let mut manifest = Manifest::load(&dir)?.unwrap_or_default();
+    // 以合并了环境变量与命令行参数的编译参数为准
+    manifest.flags = flags.clone();
+    if let Some(pch) = &manifest.pch {
+        pch::precompile(&dir, toolchain, std, &pch.headers, &manifest.flags, &[profile])?;
+    }
+    let exe = std::env::consts::EXE_SUFFIX;
+
+    if !tests {
+        let output = dir.join("target").join(format!("{name}{exe}"));
+        compile(toolchain, std, profile, &manifest, &sources(&dir, manifest.layout, manifest.kind, Path::new(INTERACTOR))?, &[], &output)?;
+        info!("Built `{}`", output.display());
+        return Ok(());
+    }
+
+    let output = dir.join("target").join(format!("{name}_tests{exe}"));
+    compile_tests(toolchain, std, profile, &dir, &manifest, &output)?;
+
+    info!("Running `{}`", output.display());
+    let mut cmd = Command::new(&output);
+    cmd.stdout(message::child_stdout());
+    message::command_run(&cmd);
+    let status = cmd.status()
+        .with_context(|| format!("Failed to evaluate `{}`", output.display()))?;
+    if !status.success() {
+        bail!("Tests failed: {status}");
+    }
+    Ok(())
+}
+
+/// 编译项目的单元测试，测试与项目源文件一起编译，项目的main函数与库的示例程序除外
+pub(crate) fn compile_tests(toolchain: &Path, std: &str, profile: &Profile, dir: &Path, manifest: &Manifest, output: &Path) -> anyhow::Result<()> {
+    let Some(framework) = manifest.test_framework else {
+        return Err(XcppError::Config("No test framework in `xcpp.toml`, create the project with `--test-framework`".to_owned()).into());
+    };
+    let main = dir.join(manifest.layout.source_dir()).join("main.cpp");
+    let mut test_sources: Vec<_> = sources(dir, manifest.layout, Kind::Bin, Path::new(INTERACTOR))?
+        .into_iter()
+        .filter(|source| *source != main)
+        .collect();
+    collect_sources(&dir.join("tests"), &[], &mut test_sources)?;
+
+    let include = format!("-I{VENDOR_DIR}");
+    let args: Vec<&str> = std::iter::once(include.as_str())
+        .chain(framework.libs().iter().copied())
+        .collect();
+    compile(toolchain, std, profile, manifest, &test_sources, &args, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        };
+        for name in ["main.cpp", "interactor.cpp", "judge.cpp", "a.h", "lib/a.cpp", "tests/t.cpp", "target/pch/x.cpp", ".git/y.cpp"] {
+            write(name);
+        }
+
+        let relative = |sources: Vec<PathBuf>| -> Vec<String> {
+            sources.iter()
+                .map(|path| path.strip_prefix(dir.path()).unwrap().to_str().unwrap().replace('\\', "/"))
+                .collect()
+        };
+        let interactor = Path::new(INTERACTOR);
+        assert_eq!(relative(sources(dir.path(), Layout::Flat, Kind::Bin, interactor).unwrap()), ["judge.cpp", "lib/a.cpp", "main.cpp"]);
+        // `--interactor judge.cpp`
+        assert_eq!(relative(sources(dir.path(), Layout::Flat, Kind::Bin, Path::new("judge.cpp")).unwrap()), ["interactor.cpp", "lib/a.cpp", "main.cpp"]);
+
+        for name in ["src/main.cpp", "src/lib/b.cpp", "src/lib/b.h"] {
+            write(name);
+        }
+        assert_eq!(relative(sources(dir.path(), Layout::Src, Kind::Bin, interactor).unwrap()), ["src/lib/b.cpp", "src/main.cpp"]);
+
+        write("example/main.cpp");
+        assert_eq!(
+            relative(sources(dir.path(), Layout::Src, Kind::Staticlib, interactor).unwrap()),
+            ["example/main.cpp", "src/lib/b.cpp", "src/main.cpp"]
+        );
+    }
+}
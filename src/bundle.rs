@@ -0,0 +1,263 @@
+use std::{collections::HashSet, path::{Path, PathBuf}};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+
+use crate::{create_file_with_content, project::Manifest};
+
+/// 把`entry`及其递归包含的项目内头文件合并为单个源文件`output`，`entry`默认为源文件目录下的`main.cpp`
+pub(crate) fn bundle(entry: Option<&Path>, output: &Path, strip_local: bool) -> anyhow::Result<()> {
+    let root = std::env::current_dir().context("Failed to get current directory")?;
+    let layout = Manifest::load(&root)?.unwrap_or_default().layout;
+    let entry = match entry {
+        Some(entry) => entry.to_path_buf(),
+        None => Path::new(layout.source_dir()).join("main.cpp"),
+    };
+    let project_dirs: Vec<_> = layout.include_dirs().iter().map(|dir| root.join(dir)).collect();
+    let mut bundler = Bundler {
+        include_dirs: std::iter::once(root).chain(project_dirs.iter().cloned()).collect(),
+        project_dirs,
+        pragma_once: HashSet::new(),
+        guards: HashSet::new(),
+        stack: Vec::new(),
+        out: String::new(),
+    };
+    bundler.inline(&entry)?;
+
+    let content = if strip_local {
+        strip_local_blocks(&bundler.out)?
+    } else {
+        bundler.out
+    };
+    create_file_with_content(output, &content)?;
+    info!("Bundled `{}` into `{}`", entry.display(), output.display());
+    Ok(())
+}
+
+struct Bundler {
+    /// 当前文件所在目录之后，依次查找`#include "..."`的目录
+    include_dirs: Vec<PathBuf>,
+    /// 项目的`-I`目录，其中的头文件即使以`#include <...>`包含也会展开
+    project_dirs: Vec<PathBuf>,
+    /// 已经展开过的`#pragma once`头文件
+    pragma_once: HashSet<PathBuf>,
+    /// 已经定义过的include guard宏
+    guards: HashSet<String>,
+    /// 正在展开的文件，用于发现循环包含
+    stack: Vec<PathBuf>,
+    out: String,
+}
+
+impl Bundler {
+    fn inline(&mut self, path: &Path) -> anyhow::Result<()> {
+        let path = path.canonicalize()
+            .with_context(|| format!("Failed to find `{}`", path.display()))?;
+        if self.pragma_once.contains(&path) {
+            return Ok(());
+        }
+        if self.stack.contains(&path) {
+            bail!("Circular include of `{}`", path.display());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))?;
+
+        if let Some(guard) = include_guard(&content) {
+            // 同名的include guard已经定义过，再次展开只会得到空内容
+            if !self.guards.insert(guard.to_owned()) {
+                return Ok(());
+            }
+        }
+
+        self.stack.push(path.clone());
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        for line in content.lines() {
+            match directive(line) {
+                Some(("pragma", "once")) => {
+                    self.pragma_once.insert(path.clone());
+                }
+                Some(("include", arg)) if arg.starts_with('"') => {
+                    let name = arg.trim_start_matches('"').split('"').next().unwrap_or_default();
+                    match self.resolve(&dir, name) {
+                        Some(header) => self.inline(&header)?,
+                        None => {
+                            warn!("`{name}` included by `{}` is not a project header, leaving it as is", path.display());
+                            self.push_line(line);
+                        }
+                    }
+                }
+                Some(("include", arg)) if arg.starts_with('<') => {
+                    let name = arg.trim_start_matches('<').split('>').next().unwrap_or_default();
+                    match self.project_dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file()) {
+                        Some(header) => self.inline(&header)?,
+                        None => self.push_line(line),
+                    }
+                }
+                _ => self.push_line(line),
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn resolve(&self, dir: &Path, name: &str) -> Option<PathBuf> {
+        std::iter::once(dir)
+            .chain(self.include_dirs.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+}
+
+/// 解析预处理指令，返回(指令名, 参数)
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+    let (name, arg) = rest.split_at(end);
+    // 去掉行尾注释
+    let arg = arg.split("//").next().unwrap_or_default().trim();
+    Some((name, arg))
+}
+
+/// 识别形如`#ifndef X` `#define X` ... `#endif`的include guard，返回宏名
+fn include_guard(content: &str) -> Option<&str> {
+    let mut lines = content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//"));
+    let guard = match directive(lines.next()?)? {
+        ("ifndef", guard) => guard,
+        _ => return None,
+    };
+    match directive(lines.next()?)? {
+        ("define", define) if define == guard => {}
+        _ => return None,
+    }
+    // guard必须包住整个文件
+    let mut depth = 1;
+    let mut closed = false;
+    for line in lines {
+        if closed {
+            return None;
+        }
+        match directive(line) {
+            Some(("if" | "ifdef" | "ifndef", _)) => depth += 1,
+            Some(("endif", _)) => {
+                depth -= 1;
+                closed = depth == 0;
+            }
+            _ => {}
+        }
+    }
+    closed.then_some(guard)
+}
+
+/// 删除`#ifdef LOCAL`等只在本地编译的代码，保留对应的`#else`分支
+fn strip_local_blocks(content: &str) -> anyhow::Result<String> {
+    /// `Local(keep)`为判断`LOCAL`的条件块，`keep`表示当前分支是否保留
+    enum Frame {
+        Local(bool),
+        Other,
+    }
+
+    let mut out = String::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let visible = stack.iter().all(|frame| !matches!(frame, Frame::Local(false)));
+        match directive(line) {
+            Some((kind @ ("ifdef" | "ifndef" | "if"), arg)) => {
+                let local = match (kind, arg) {
+                    ("ifdef", "LOCAL") => Some(false),
+                    ("ifndef", "LOCAL") => Some(true),
+                    ("if", "defined(LOCAL)" | "defined LOCAL") => Some(false),
+                    ("if", "!defined(LOCAL)" | "!defined LOCAL") => Some(true),
+                    _ => None,
+                };
+                match local {
+                    Some(keep) => stack.push(Frame::Local(keep)),
+                    None => {
+                        stack.push(Frame::Other);
+                        if visible {
+                            out.push_str(line);
+                            out.push('\n');
+                        }
+                    }
+                }
+                continue;
+            }
+            Some(("else", _)) => {
+                if let Some(Frame::Local(keep)) = stack.last_mut() {
+                    *keep = !*keep;
+                    continue;
+                }
+            }
+            Some(("elif", _)) => {
+                if let Some(Frame::Local(_)) = stack.last() {
+                    bail!("Line {}: `#elif` after a `LOCAL` condition is not supported", i + 1);
+                }
+            }
+            Some(("endif", _)) => {
+                if let Some(Frame::Local(_)) = stack.pop() {
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        if visible {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_test() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| std::fs::write(dir.path().join(name), content).unwrap();
+        write("a.h", "#pragma once\nint a();\n");
+        write("b.h", "#ifndef B_H\n#define B_H\n#include \"a.h\"\nint b();\n#endif\n");
+        write("main.cpp", "#include <vector>\n#include \"a.h\"\n#include \"b.h\"\n#include \"b.h\"\nint main() {}\n");
+
+        let mut bundler = Bundler {
+            include_dirs: Vec::new(),
+            project_dirs: Vec::new(),
+            pragma_once: HashSet::new(),
+            guards: HashSet::new(),
+            stack: Vec::new(),
+            out: String::new(),
+        };
+        bundler.inline(&dir.path().join("main.cpp")).unwrap();
+        assert_eq!(
+            bundler.out,
+            "#include <vector>\nint a();\n#ifndef B_H\n#define B_H\nint b();\n#endif\nint main() {}\n"
+        );
+    }
+
+    #[test]
+    fn strip_local_blocks_test() {
+        let content = "\
+#ifdef LOCAL
+#include \"debug.h\"
+#else
+#define dbg(...)
+#endif
+#ifndef LOCAL
+#ifdef _WIN32
+int win;
+#endif
+#endif
+int main() {}
+";
+        assert_eq!(
+            strip_local_blocks(content).unwrap(),
+            "#define dbg(...)\n#ifdef _WIN32\nint win;\n#endif\nint main() {}\n"
+        );
+    }
+}
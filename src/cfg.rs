@@ -3,16 +3,200 @@ pub(crate) struct ConfigFile {
     pub(crate) content: &'static str,
 }
 
+/// 一组编译参数，对应tasks.json中的一个g++任务，`xcpp pch`也按它预编译头文件
+pub(crate) struct Profile {
+    pub(crate) name: &'static str,
+    pub(crate) label: &'static str,
+    pub(crate) flags: &'static [&'static str],
+}
+
+pub(crate) const PROFILES: [Profile; 9] = [
+    Profile { name: "O0", label: "C++: -O0", flags: &["-g", "-Wall", "-DLOCAL"] },
+    Profile { name: "O1", label: "C/C++: -O1", flags: &["-g", "-DLOCAL", "-Wall", "-O1"] },
+    Profile { name: "O2", label: "C/C++: -O2", flags: &["-g", "-DLOCAL", "-Wall", "-O2"] },
+    // "-march=native" 生成的binary将与本地机器相关，不建议使用
+    // "-Wl,--stack=536870912" 扩栈
+    Profile { name: "O3", label: "C/C++: -O3", flags: &["-g", "-DLOCAL", "-Wall", "-O3"] },
+    Profile { name: "release", label: "Release", flags: &["-static", "-Wall", "-O3"] },
+    // sanitizer需要工具链提供对应的运行时库，MinGW通常不支持，不支持时不会生成
+    Profile { name: "asan", label: "C/C++: ASan", flags: &["-g", "-DLOCAL", "-Wall", "-O1", "-fsanitize=address", "-fno-omit-frame-pointer"] },
+    Profile { name: "ubsan", label: "C/C++: UBSan", flags: &["-g", "-DLOCAL", "-Wall", "-O1", "-fsanitize=undefined", "-fno-sanitize-recover=undefined"] },
+    Profile { name: "tsan", label: "C/C++: TSan", flags: &["-g", "-DLOCAL", "-Wall", "-O1", "-fsanitize=thread"] },
+    // 运行后生成gcov使用的.gcda，`xcpp coverage`据此统计行覆盖率
+    Profile { name: "coverage", label: "C/C++: Coverage", flags: &["-g", "-DLOCAL", "-Wall", "-O0", "--coverage"] },
+];
+
+impl Profile {
+    pub(crate) fn is_sanitizer(&self) -> bool {
+        self.flags.iter().any(|flag| flag.starts_with("-fsanitize="))
+    }
+}
+
+/// 所有编译参数的名称，用于`--profile`
+pub(crate) fn profile_names() -> Vec<&'static str> {
+    PROFILES.iter().map(|profile| profile.name).collect()
+}
+
+/// tasks.json中的g++任务，`{{args}}`为`Profile::flags`
+pub(crate) const TASK: &str =
+r#"        {
+            "type": "cppbuild",
+            "label": "{{label}}",
+            "command": "{{g++}}",
+            "args": [
+                "-fdiagnostics-color=always",
+                "-std={{stdc++}}",
+{{args}}
+                "-I${workspaceFolder}/target/pch",
+                "{{task_sources}}",{{link_args}}
+                "-o",
+                "${workspaceFolder}/target/${workspaceFolderBasename}.exe"
+            ],
+            "options": {
+                "cwd": "${fileDirname}"
+            },
+            "problemMatcher": [
+                "$gcc"
+            ],
+            "group": "build",
+            "detail": "g++ -std={{stdc++}} {{detail}}"
+        }"#;
+
+/// `src`布局下tasks.json中的任务，通过makefile编译整个源码目录
+pub(crate) const MAKE_TASK: &str =
+r#"        {
+            "type": "shell",
+            "label": "{{label}}",
+            "command": "{{make}}",
+            "args": [
+                "MODE={{profile}}"
+            ],
+            "options": {
+                "cwd": "${workspaceFolder}"
+            },
+            "problemMatcher": [
+                "$gcc"
+            ],
+            "group": "build",
+            "detail": "g++ -std={{stdc++}} {{detail}}"
+        }"#;
+
+/// 配置了单元测试框架时tasks.json中编译、运行测试的任务
+pub(crate) const TEST_TASKS: &str =
+r#"        {
+            "type": "shell",
+            "label": "Build tests",
+            "command": "{{make}}",
+            "args": [
+                "tests"
+            ],
+            "options": {
+                "cwd": "${workspaceFolder}"
+            },
+            "problemMatcher": [
+                "$gcc"
+            ],
+            "group": "build",
+            "detail": "make tests"
+        },
+        {
+            "type": "shell",
+            "label": "Run tests",
+            "command": "{{make}}",
+            "args": [
+                "check"
+            ],
+            "options": {
+                "cwd": "${workspaceFolder}"
+            },
+            "problemMatcher": [
+                "$gcc"
+            ],
+            "group": "test",
+            "detail": "make check"
+        }"#;
+
+/// 能找到valgrind时tasks.json中以memcheck运行项目的任务，`{{label}}`为编译所用的任务，`{{exe}}`为它生成的可执行文件的后缀
+pub(crate) const MEMCHECK_TASK: &str =
+r#"        {
+            "type": "shell",
+            "label": "Memcheck",
+            "command": "valgrind --leak-check=full --error-exitcode=1 \"${workspaceFolder}/target/${workspaceFolderBasename}{{exe}}\" < data.in > data.out",
+            "options": {
+                "cwd": "{{launch_cwd}}"
+            },
+            "problemMatcher": [],
+            "dependsOn": "{{label}}",
+            "group": "test",
+            "detail": "valgrind --leak-check=full"
+        }"#;
+
+/// launch.json中调试sanitizer版本的配置，gdb下LeakSanitizer无法工作，因此关闭内存泄漏检测。
+/// 以逗号开头，接在前一项配置之后，避免JSON中出现多余的逗号
+pub(crate) const SANITIZER_LAUNCH: &str =
+r#",
+        {
+            "name": "{{label}} Testing",
+            "type": "cppdbg",
+            "request": "launch",
+            "program": "${workspaceFolder}/target/${workspaceFolderBasename}.exe",
+            "args": ["<", "data.in", ">", "data.out"],
+            "stopAtEntry": false,
+            "cwd": "{{launch_cwd}}",
+            "environment": [
+                {
+                    "name": "ASAN_OPTIONS",
+                    "value": "detect_leaks=0"
+                }
+            ],
+            "externalConsole": false,
+            "MIMode": "gdb",
+            "miDebuggerPath": "{{gdb}}",
+            "setupCommands": [
+                {
+                    "description": "为 gdb 启用整齐打印",
+                    "text": "-enable-pretty-printing",
+                    "ignoreFailures": true
+                }
+            ],
+            "preLaunchTask": "{{label}}"
+        }"#;
+
+/// 配置了单元测试框架时launch.json中调试测试程序的配置，同样以逗号开头
+pub(crate) const TEST_LAUNCH: &str =
+r#",
+        {
+            "name": "Debug tests",
+            "type": "cppdbg",
+            "request": "launch",
+            "program": "${workspaceFolder}/target/${workspaceFolderBasename}_tests.exe",
+            "args": [],
+            "stopAtEntry": false,
+            "cwd": "${workspaceFolder}",
+            "environment": [],
+            "externalConsole": false,
+            "MIMode": "gdb",
+            "miDebuggerPath": "{{gdb}}",
+            "setupCommands": [
+                {
+                    "description": "为 gdb 启用整齐打印",
+                    "text": "-enable-pretty-printing",
+                    "ignoreFailures": true
+                }
+            ],
+            "preLaunchTask": "Build tests"
+        }"#;
+
 pub(crate) const FILES: [ConfigFile; 5] = [
     ConfigFile { path: ".vscode/c_cpp_properties.json", content: 
 r#"{
     "configurations": [
         {
             "name": "Win32",
-            "includePath": [
+            "includePath": [{{include_path}}
                 "${workspaceFolder}/**"
             ],
-            "defines": [
+            "defines": [{{defines}}
                 "_DEBUG",
                 "UNICODE",
                 "_UNICODE"
@@ -39,7 +223,7 @@ r#"{
             "program": "${workspaceFolder}/target/${workspaceFolderBasename}.exe",
             "args": ["<", "data.in", ">", "data.out"],
             "stopAtEntry": false,
-            "cwd": "${fileDirname}",
+            "cwd": "{{launch_cwd}}",
             "environment": [],
             "externalConsole": false,
             "MIMode": "gdb",
@@ -65,7 +249,7 @@ r#"{
             "program": "${workspaceFolder}/target/${workspaceFolderBasename}.exe",
             "args": ["<", "data.in", ">", "data.out"],
             "stopAtEntry": false,
-            "cwd": "${fileDirname}",
+            "cwd": "{{launch_cwd}}",
             "environment": [],
             "externalConsole": false,
             "MIMode": "gdb",
@@ -91,7 +275,7 @@ r#"{
             "program": "${workspaceFolder}/target/${workspaceFolderBasename}.exe",
             "args": ["<", "data.in", ">", "data.out"],
             "stopAtEntry": false,
-            "cwd": "${fileDirname}",
+            "cwd": "{{launch_cwd}}",
             "environment": [],
             "externalConsole": false,
             "MIMode": "gdb",
@@ -117,7 +301,7 @@ r#"{
             "program": "${workspaceFolder}/target/${workspaceFolderBasename}.exe",
             "args": ["<", "data.in", ">", "data.out"],
             "stopAtEntry": false,
-            "cwd": "${fileDirname}",
+            "cwd": "{{launch_cwd}}",
             "environment": [],
             "externalConsole": false,
             "MIMode": "gdb",
@@ -143,7 +327,7 @@ r#"{
             "program": "${workspaceFolder}/target/${workspaceFolderBasename}.exe",
             "args": ["<", "data.in", ">", "data.out"],
             "stopAtEntry": false,
-            "cwd": "${fileDirname}",
+            "cwd": "{{launch_cwd}}",
             "environment": [],
             "externalConsole": false,
             "MIMode": "gdb",
@@ -169,7 +353,7 @@ r#"{
             "program": "${fileDirname}/${fileBasenameNoExtension}.exe",
             "args": ["<", "data.in", ">", "data.out"],
             "stopAtEntry": false,
-            "cwd": "${fileDirname}",
+            "cwd": "{{launch_cwd}}",
             "environment": [],
             "externalConsole": true,
             "MIMode": "gdb",
@@ -187,7 +371,7 @@ r#"{
                 }
             ],
             "preLaunchTask": "Release"
-        },
+        }{{sanitizer_launch}}{{test_launch}}
     ]
 }
 "# },
@@ -321,7 +505,7 @@ r#"{
             "command": "{{make}}",
             "args": [],
             "options": {
-                "cwd": "${fileDirname}"
+                "cwd": "${workspaceFolder}"
             },
             "problemMatcher": [
                 "$gcc"
@@ -329,155 +513,435 @@ r#"{
             "group": "build",
             "detail": "wow, make"
         },
-        {
-            "type": "cppbuild",
-            "label": "C++: -O0",
-            "command": "{{g++}}",
-            "args": [
-                "-fdiagnostics-color=always",
-                "-std={{stdc++}}",
-                "-g",
-                "-Wall",
-                "-DLOCAL",
-                "${fileDirname}/*.cpp",
-                // "${fileDirname}/*.c",
-                "-o",
-                "${workspaceFolder}/target/${workspaceFolderBasename}.exe"
-            ],
-            "options": {
-                "cwd": "${fileDirname}"
-            },
-            "problemMatcher": [
-                "$gcc"
-            ],
-            "group": "build",
-            "detail": "g++ -std={{stdc++}} -g -O0"
-        },
-        {
-            "type": "cppbuild",
-            "label": "C/C++: -O1",
-            "command": "{{g++}}",
-            "args": [
-                "-fdiagnostics-color=always",
-                "-std={{stdc++}}",
-                "-g",
-                "-DLOCAL",
-                "-Wall",
-                "-O1",
-                "${fileDirname}/*.cpp",
-                "-o",
-                "${workspaceFolder}/target/${workspaceFolderBasename}.exe"
-            ],
-            "options": {
-                "cwd": "${fileDirname}"
-            },
-            "problemMatcher": [
-                "$gcc"
-            ],
-            "group": "build",
-            "detail": "g++ -std={{stdc++}} -g -O1"
-        },
-        {
-            "type": "cppbuild",
-            "label": "C/C++: -O2",
-            "command": "{{g++}}",
-            "args": [
-                "-fdiagnostics-color=always",
-                "-std={{stdc++}}",
-                "-g",
-                "-DLOCAL",
-                "-Wall",
-                "-O2",
-                "${fileDirname}/*.cpp",
-                "-o",
-                "${workspaceFolder}/target/${workspaceFolderBasename}.exe"
-            ],
-            "options": {
-                "cwd": "${fileDirname}"
-            },
-            "problemMatcher": [
-                "$gcc"
-            ],
-            "group": "build",
-            "detail": "g++ -std={{stdc++}} -g -O2"
-        },
-        {
-            "type": "cppbuild",
-            "label": "C/C++: -O3",
-            "command": "{{g++}}",
-            "args": [
-                "-fdiagnostics-color=always",
-                "-std={{stdc++}}",
-                "-g",
-                "-DLOCAL",
-                "-Wall",
-                "-O3",
-                //"-march=native", 生成的binary将与本地机器相关，不建议使用
-                // "-Wl,--stack=536870912", 扩栈
-                // "${file}",
-                "${fileDirname}/*.cpp",
-                // "${fileDirname}/*.c",
-                "-o",
-                "${workspaceFolder}/target/${workspaceFolderBasename}.exe"
-            ],
-            "options": {
-                "cwd": "${fileDirname}"
-            },
-            "problemMatcher": [
-                "$gcc"
-            ],
-            "group": "build",
-            "detail": "g++ -std={{stdc++}} -g -O3"
-        },
-        {
-            "type": "cppbuild",
-            "label": "Release",
-            "command": "{{g++}}",
-            "args": [
-                "-fdiagnostics-color=always",
-                "-std={{stdc++}}",
-                "-static",
-                // "-g",
-                "-Wall",
-                "-O3",
-                // "${file}",
-                "${fileDirname}/*.cpp",
-                // "${fileDirname}/*.c",
-                "-o",
-                "${workspaceFolder}/target/${workspaceFolderBasename}.exe"
-            ],
-            "options": {
-                "cwd": "${fileDirname}"
-            },
-            "problemMatcher": [
-                "$gcc"
-            ],
-            "group": "build",
-            "detail": "g++ -std={{stdc++}} -static -O3"
-        },
+{{tasks}}
     ]
 }
 "# },
     ConfigFile { path: "makefile", content: 
-r#"TARPATH = {{current_dir}}/target/
-CXX = {{g++}}
+r#"TARPATH = {{tarpath}}
+CXX = {{cxx}}
+AR = {{ar}}
 EXEC = {{project}}
-SRC = $(wildcard *.cpp)
-OBJ = $(patsubst %.cpp, $(TARPATH)%.o, $(SRC))
+# bin、staticlib、sharedlib或header-only，库项目会额外编译链接它的example/
+KIND = {{kind}}
+
+# make MODE=<name> 使用与tasks.json中同名任务相同的编译参数
+MODE ?= O1
+{{modes}}
+CXXFLAGS += -I$(TARPATH)pch{{includes}}
+# --link指定的库
+LINKS = {{links}}
+OBJPATH = $(TARPATH)obj/$(MODE)/
+
+ifeq ($(OS),Windows_NT)
+    EXE = .exe
+    MKDIR = if not exist "$(subst /,\,$(1))" mkdir "$(subst /,\,$(1))"
+    RMDIR = if exist "$(subst /,\,$(1))" rmdir /s /q "$(subst /,\,$(1))"
+    RM = if exist "$(subst /,\,$(1))" del /q "$(subst /,\,$(1))"
+    CP = copy /y "$(subst /,\,$(1))" "$(subst /,\,$(2))" >nul
+    DLL = .dll
+else
+    EXE =
+    MKDIR = mkdir -p "$(1)"
+    RMDIR = rm -rf "$(1)"
+    RM = rm -f "$(1)"
+    CP = cp "$(1)" "$(2)"
+    DLL = .so
+endif
+
+# 递归查找源文件
+rwildcard = $(wildcard $(1)$(2)) $(foreach d,$(wildcard $(1)*),$(call rwildcard,$(d)/,$(2)))
+SRC = {{sources}}
+EXAMPLE_SRC =
+ifneq ($(KIND),bin)
+    EXAMPLE_SRC = $(call rwildcard,example/,*.cpp)
+endif
+OBJ = $(patsubst %.cpp,$(OBJPATH)%.o,$(SRC))
+EXAMPLE_OBJ = $(patsubst %.cpp,$(OBJPATH)%.o,$(EXAMPLE_SRC))
+
+# 单元测试与项目源文件一起编译，项目的main函数除外
+TEST_FRAMEWORK = {{test_framework}}
+TEST_OBJ =
+ifneq ($(TEST_FRAMEWORK),)
+    CXXFLAGS += -I{{vendor_dir}}
+    TEST_SRC = $(call rwildcard,tests/,*.cpp) $(filter-out {{main}},$(SRC))
+    TEST_OBJ = $(patsubst %.cpp,$(OBJPATH)%.o,$(TEST_SRC))
+    TEST_LIBS = {{test_libs}}
+endif
+TEST_BIN = $(TARPATH)$(EXEC)_tests$(EXE)
+
+DEP = $(OBJ:.o=.d) $(EXAMPLE_OBJ:.o=.d) $(TEST_OBJ:.o=.d)
+OBJDIRS = $(patsubst %/,%,$(sort $(dir $(OBJ) $(EXAMPLE_OBJ) $(TEST_OBJ))))
+# 每种MODE分别链接，再复制到调试配置使用的$(TARPATH)$(EXEC)
+LINKED = $(OBJPATH)$(EXEC)$(EXE)
+BIN = $(TARPATH)$(EXEC)$(EXE)
+
+LIB =
+ifeq ($(KIND),staticlib)
+    LIB = lib$(EXEC).a
+endif
+ifeq ($(KIND),sharedlib)
+    LIB = lib$(EXEC)$(DLL)
+    # 动态库不能静态链接
+    CXXFLAGS := $(filter-out -static,$(CXXFLAGS))
+    ifneq ($(OS),Windows_NT)
+        CXXFLAGS += -fPIC
+        # 示例程序从$(TARPATH)加载复制过去的动态库，Windows下dll与exe位于同一目录即可
+        LDLIBS = -Wl,-rpath,$(TARPATH)
+    endif
+endif
+ifneq ($(LIB),)
+    LDLIBS += -L$(OBJPATH) -l$(EXEC)
+endif
+
+.PHONY: all debug release tests check clean
+all: $(LINKED)
+	@$(call CP,$(LINKED),$(BIN))
+ifneq ($(LIB),)
+	@$(call CP,$(OBJPATH)$(LIB),$(TARPATH)$(LIB))
+endif
+
+debug:
+	$(MAKE) MODE=O1
+
+release:
+	$(MAKE) MODE=release
 
-CFLAGS = -c -fdiagnostics-color=always -std={{stdc++}} -g -O1 -Wall -DLOCAL
+ifeq ($(KIND),bin)
+$(LINKED): $(OBJ) | $(OBJDIRS)
+	$(CXX) $(CXXFLAGS) -o $@ $(OBJ) $(LINKS)
+else
+$(LINKED): $(EXAMPLE_OBJ) $(addprefix $(OBJPATH),$(LIB)) | $(OBJDIRS)
+	$(CXX) $(CXXFLAGS) -o $@ $(EXAMPLE_OBJ) $(LDLIBS) $(LINKS)
+endif
 
-$(TARPATH)$(EXEC): $(OBJ)
-	$(CXX) -o $@ $^
+tests: $(TEST_BIN)
 
-$(TARPATH)%.o: %.cpp
-	$(CXX) $(CFLAGS) $< -o $@
+check: $(TEST_BIN)
+	$(TEST_BIN)
 
+$(TEST_BIN): $(TEST_OBJ) | $(OBJDIRS)
+	$(CXX) $(CXXFLAGS) -o $@ $(TEST_OBJ) $(TEST_LIBS) $(LINKS)
+
+$(OBJPATH)lib$(EXEC).a: $(OBJ) | $(OBJDIRS)
+	$(AR) rcs $@ $(OBJ)
+
+$(OBJPATH)lib$(EXEC)$(DLL): $(OBJ) | $(OBJDIRS)
+	$(CXX) $(CXXFLAGS) -shared -o $@ $(OBJ) $(LINKS)
+
+# -MMD -MP 生成头文件依赖，修改头文件后重新编译包含它的源文件
+$(OBJPATH)%.o: %.cpp | $(OBJDIRS)
+	$(CXX) $(CXXFLAGS) -MMD -MP -c $< -o $@
+
+$(OBJDIRS):
+	@$(call MKDIR,$@)
 
-.PHONY: clean
-TARPATH2 = $(subst /,\,$(TARPATH))
 clean:
-	del $(TARPATH2)*.o $(TARPATH2)$(EXEC).exe
+	@$(call RMDIR,$(TARPATH)obj)
+	@$(call RM,$(BIN))
+	@$(call RM,$(TEST_BIN))
+ifneq ($(LIB),)
+	@$(call RM,$(TARPATH)$(LIB))
+endif
+
+-include $(DEP)
+"# },
+];
+/// 库项目中链接库的示例程序，调试配置运行的就是它
+const EXAMPLE: ConfigFile = ConfigFile { path: "example/main.cpp", content:
+r#"#include <iostream>
+#include <{{project}}/{{project}}.h>
+
+int main() {
+    std::cout << "1 + 2 = " << {{namespace}}::add(1, 2) << '\n';
+    return 0;
+}
+"# };
+
+/// 库项目的公开头文件、源文件与链接它的示例程序，`{{namespace}}`为由项目名得到的合法标识符
+pub(crate) const LIBRARY_FILES: [ConfigFile; 3] = [
+    ConfigFile { path: "include/{{project}}/{{project}}.h", content:
+r#"#pragma once
+
+namespace {{namespace}} {
+
+int add(int a, int b);
+
+}
+"# },
+    ConfigFile { path: "src/{{project}}.cpp", content:
+r#"#include <{{project}}/{{project}}.h>
+
+namespace {{namespace}} {
+
+int add(int a, int b) {
+    return a + b;
+}
+
+}
+"# },
+    EXAMPLE,
+];
+
+/// 只有头文件的库项目
+pub(crate) const HEADER_ONLY_FILES: [ConfigFile; 2] = [
+    ConfigFile { path: "include/{{project}}/{{project}}.h", content:
+r#"#pragma once
+
+namespace {{namespace}} {
+
+inline int add(int a, int b) {
+    return a + b;
+}
+
+}
+"# },
+    EXAMPLE,
+];
+
+/// `xcpp add`使用的模板，`path`为模板名。
+/// 可以放在项目下的`.xcpp/templates/`或全局配置目录下的`templates/`中覆盖
+pub(crate) const ITEMS: [ConfigFile; 6] = [
+    ConfigFile { path: "class.h", content:
+r#"#pragma once
+{{namespace_begin}}
+class {{name}} {
+public:
+    {{name}}();
+    ~{{name}}();
+};
+{{namespace_end}}"# },
+    ConfigFile { path: "class.cpp", content:
+r#"#include {{include}}
+{{namespace_begin}}
+{{name}}::{{name}}() {
+}
+
+{{name}}::~{{name}}() {
+}
+{{namespace_end}}"# },
+    ConfigFile { path: "header.h", content:
+r#"#pragma once
+{{namespace_begin}}
+{{namespace_end}}"# },
+    ConfigFile { path: "doctest_test.cpp", content:
+r#"#include "doctest.h"
+
+TEST_CASE("{{name}}") {
+    CHECK(1 + 1 == 2);
+}
+"# },
+    ConfigFile { path: "catch2_test.cpp", content:
+r#"#include "catch.hpp"
+
+TEST_CASE("{{name}}") {
+    REQUIRE(1 + 1 == 2);
+}
+"# },
+    ConfigFile { path: "gtest_test.cpp", content:
+r#"#include <gtest/gtest.h>
+
+TEST({{suite}}, Works) {
+    EXPECT_EQ(1 + 1, 2);
+}
+"# },
+];
+
+/// doctest测试程序的main函数
+pub(crate) const DOCTEST_MAIN: ConfigFile = ConfigFile { path: "tests/test_main.cpp", content:
+r#"#define DOCTEST_CONFIG_IMPLEMENT_WITH_MAIN
+#include "doctest.h"
+"# };
+
+/// Catch2测试程序的main函数
+pub(crate) const CATCH2_MAIN: ConfigFile = ConfigFile { path: "tests/test_main.cpp", content:
+r#"#define CATCH_CONFIG_MAIN
+#include "catch.hpp"
+"# };
+
+/// 项目模板，在`FILES`之外提供初始的源文件，内容同样会被填充
+pub(crate) struct ProjectTemplate {
+    pub(crate) name: &'static str,
+    pub(crate) files: &'static [ConfigFile],
+}
+
+pub(crate) const TEMPLATES: [ProjectTemplate; 2] = [
+    ProjectTemplate { name: "default", files: &[
+        ConfigFile { path: "main.cpp", content:
+r#"#include <iostream>
+using namespace std;
+
+int main(){
+    cout << "Hello, World" << '\n';
+    return 0;
+}
+"# },
+    ] },
+    ProjectTemplate { name: "cp", files: &[
+        ConfigFile { path: "main.cpp", content:
+r#"#include <bits/stdc++.h>
+using namespace std;
+
+#ifdef LOCAL
+#include "debug.h"
+#else
+#define dbg(...)
+#endif
+
+using ll = long long;
+
+void solve() {
+    
+}
+
+int main() {
+    ios::sync_with_stdio(false);
+    cin.tie(nullptr);
+
+    int T = 1;
+    cin >> T;
+    while (T--) {
+        solve();
+    }
+    return 0;
+}
+"# },
+        ConfigFile { path: "debug.h", content:
+r#"// dbg(a, b, ...) prints `[line] a, b = value_a, value_b` to stderr.
+// Only included when compiled with -DLOCAL.
+#pragma once
+#include <cstddef>
+#include <iostream>
+#include <queue>
+#include <stack>
+#include <string>
+#include <tuple>
+#include <utility>
+
+namespace xcpp_debug {
+
+struct low {};
+struct high : low {};
+
+template <class T> void print(std::ostream &os, const T &x);
+
+inline void print_one(std::ostream &os, const std::string &s, high) { os << '"' << s << '"'; }
+inline void print_one(std::ostream &os, const char *s, high) { os << '"' << s << '"'; }
+inline void print_one(std::ostream &os, char c, high) { os << '\'' << c << '\''; }
+inline void print_one(std::ostream &os, bool b, high) { os << (b ? "true" : "false"); }
+
+template <class A, class B>
+void print_one(std::ostream &os, const std::pair<A, B> &p, high) {
+    os << '(';
+    print(os, p.first);
+    os << ", ";
+    print(os, p.second);
+    os << ')';
+}
+
+template <class Tuple, std::size_t I, std::size_t N>
+struct tuple_printer {
+    static void print_from(std::ostream &os, const Tuple &t) {
+        if (I > 0) os << ", ";
+        print(os, std::get<I>(t));
+        tuple_printer<Tuple, I + 1, N>::print_from(os, t);
+    }
+};
+
+template <class Tuple, std::size_t N>
+struct tuple_printer<Tuple, N, N> {
+    static void print_from(std::ostream &, const Tuple &) {}
+};
+
+template <class... Ts>
+void print_one(std::ostream &os, const std::tuple<Ts...> &t, high) {
+    os << '(';
+    tuple_printer<std::tuple<Ts...>, 0, sizeof...(Ts)>::print_from(os, t);
+    os << ')';
+}
+
+// stack, queue and priority_queue are printed from a copy, top/front first
+template <class T, class C>
+void print_one(std::ostream &os, std::stack<T, C> s, high) {
+    os << '[';
+    for (bool first = true; !s.empty(); s.pop(), first = false) {
+        if (!first) os << ", ";
+        print(os, s.top());
+    }
+    os << ']';
+}
+
+template <class T, class C>
+void print_one(std::ostream &os, std::queue<T, C> q, high) {
+    os << '[';
+    for (bool first = true; !q.empty(); q.pop(), first = false) {
+        if (!first) os << ", ";
+        print(os, q.front());
+    }
+    os << ']';
+}
+
+template <class T, class C, class Cmp>
+void print_one(std::ostream &os, std::priority_queue<T, C, Cmp> q, high) {
+    os << '[';
+    for (bool first = true; !q.empty(); q.pop(), first = false) {
+        if (!first) os << ", ";
+        print(os, q.top());
+    }
+    os << ']';
+}
+
+template <std::size_t N>
+void print_one(std::ostream &os, const char (&s)[N], high) { os << '"' << s << '"'; }
+
+template <class T, std::size_t N>
+void print_one(std::ostream &os, const T (&a)[N], high) {
+    os << '[';
+    for (std::size_t i = 0; i < N; i++) {
+        if (i > 0) os << ", ";
+        print(os, a[i]);
+    }
+    os << ']';
+}
+
+// anything that supports operator<<
+template <class T>
+auto print_one(std::ostream &os, const T &x, high) -> decltype(os << x, void()) { os << x; }
+
+// containers: vector, set, map, ...
+template <class T>
+auto print_one(std::ostream &os, const T &c, low) -> decltype(std::begin(c), std::end(c), void()) {
+    os << '[';
+    bool first = true;
+    for (const auto &x : c) {
+        if (!first) os << ", ";
+        first = false;
+        print(os, x);
+    }
+    os << ']';
+}
+
+template <class T> void print(std::ostream &os, const T &x) { print_one(os, x, high()); }
+
+inline void print_all(std::ostream &) {}
+
+template <class T, class... Ts>
+void print_all(std::ostream &os, const T &x, const Ts &...xs) {
+    print(os, x);
+    if (sizeof...(xs) > 0) os << ", ";
+    print_all(os, xs...);
+}
+
+template <class... Ts>
+void debug_out(int line, const char *names, const Ts &...xs) {
+    std::cerr << '[' << line << "] " << names << " = ";
+    print_all(std::cerr, xs...);
+    std::cerr << std::endl;
+}
+
+} // namespace xcpp_debug
 
+#define dbg(...) xcpp_debug::debug_out(__LINE__, #__VA_ARGS__, __VA_ARGS__)
 "# },
-];
\ No newline at end of file
+    ] },
+];
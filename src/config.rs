@@ -0,0 +1,439 @@
+use std::{fmt, path::{Path, PathBuf}};
+
+use anyhow::{bail, Context};
+use log::warn;
+use serde_derive::{Serialize, Deserialize};
+use structopt::StructOpt;
+
+use crate::{cfg, cpp_standards, error::XcppError, flags::{Flags, Warnings}, message::{self, report}, project::Manifest};
+
+/// `config.toml`的格式版本，修改`MyConfig`时递增并在`MIGRATIONS`中加入迁移函数
+pub(crate) const CONFIG_VERSION: i64 = 2;
+
+/// `MIGRATIONS[i]`把版本`i + 1`的配置升级到版本`i + 2`
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize - 1] = [migrate_v1];
+
+/// 全局配置，对应`config.toml`
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub(crate) struct MyConfig {
+    /// 没有`version`的配置来自加入版本号之前的xcpp，视为版本1
+    #[serde(default)]
+    pub(crate) version: i64,
+    pub(crate) std: String,
+    pub(crate) mingw64_path: String,
+    /// 新建项目时默认使用的模板
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) template: String,
+    /// 新建项目时默认追加的编译参数
+    #[serde(default)]
+    pub(crate) flags: Flags,
+}
+
+/// 版本1没有校验手动编辑的内容，可能含有Windows风格的路径与无效的`std = "cfg"`
+fn migrate_v1(table: &mut toml::Table) {
+    if let Some(toml::Value::String(path)) = table.get_mut("mingw64_path") {
+        *path = path.replace('\\', "/");
+    }
+    if table.get("std").and_then(toml::Value::as_str) == Some("cfg") {
+        table.insert("std".to_owned(), toml::Value::String(String::new()));
+    }
+}
+
+/// 把`table`升级到`CONFIG_VERSION`，返回升级前的版本，来自更新的xcpp的配置无法读取
+fn migrate(table: &mut toml::Table) -> anyhow::Result<i64> {
+    let version = match table.get("version") {
+        Some(version) => version.as_integer().context("`version` must be an integer")?,
+        None => 1,
+    };
+    if version > CONFIG_VERSION {
+        bail!("The config is version {version} from a newer xcpp, this xcpp {} supports up to version {CONFIG_VERSION}, please update xcpp", crate::project::VERSION);
+    }
+    if version < 1 {
+        bail!("Invalid config version {version}");
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(table);
+    }
+    table.insert("version".to_owned(), toml::Value::Integer(CONFIG_VERSION));
+    Ok(version)
+}
+
+/// 读取`config.toml`，旧版本的配置会先备份为`config.toml.v<version>.bak`再升级
+pub(crate) fn load() -> anyhow::Result<MyConfig> {
+    let path = confy::get_configuration_file_path("xcpp", "config")?;
+    if !path.exists() {
+        return Ok(MyConfig { version: CONFIG_VERSION, ..Default::default() });
+    }
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read `{}`", path.display()))?;
+    let mut table: toml::Table = content.parse()
+        .with_context(|| format!("Invalid `{}`", path.display()))?;
+    let version = migrate(&mut table).with_context(|| format!("Failed to load `{}`", path.display()))?;
+    let config: MyConfig = toml::Value::Table(table).try_into()
+        .with_context(|| format!("Invalid `{}`", path.display()))?;
+    if version < CONFIG_VERSION {
+        let backup = path.with_file_name(format!("config.toml.v{version}.bak"));
+        std::fs::copy(&path, &backup)
+            .with_context(|| format!("Failed to back up `{}`", path.display()))?;
+        message::file_created(&backup);
+        let content = toml::to_string(&config).context("Failed to serialize the config")?;
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write `{}`", path.display()))?;
+        warn!("Migrated `{}` from version {version} to {CONFIG_VERSION}, the old file is kept at `{}`", path.display(), backup.display());
+    }
+    Ok(config)
+}
+
+/// 配置的来源，按优先级从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Origin {
+    Default,
+    Global,
+    Project,
+    Env,
+    Cli,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Origin::Default => "default",
+            Origin::Global => "config.toml",
+            Origin::Project => "xcpp.toml",
+            Origin::Env => "environment",
+            Origin::Cli => "command line",
+        })
+    }
+}
+
+/// 一层配置中给出的值，`None`表示该层没有设置
+#[derive(Default, Debug)]
+pub(crate) struct Layer {
+    pub(crate) std: Option<String>,
+    pub(crate) path: Option<String>,
+    pub(crate) template: Option<String>,
+    pub(crate) warnings: Option<Warnings>,
+    pub(crate) defines: Option<Vec<String>>,
+    pub(crate) include_dirs: Option<Vec<String>>,
+    pub(crate) links: Option<Vec<String>>,
+}
+
+impl Layer {
+    /// `config.toml`中为空的项视为未设置
+    fn global(config: MyConfig) -> Layer {
+        let non_empty = |value: String| Some(value).filter(|value| !value.is_empty());
+        let flags = config.flags;
+        Layer {
+            std: non_empty(config.std),
+            path: non_empty(config.mingw64_path),
+            template: non_empty(config.template),
+            warnings: Some(flags.warnings).filter(|&warnings| warnings != Warnings::default()),
+            defines: Some(flags.defines).filter(|defines| !defines.is_empty()),
+            include_dirs: Some(flags.include_dirs).filter(|dirs| !dirs.is_empty()),
+            links: Some(flags.links).filter(|links| !links.is_empty()),
+        }
+    }
+
+    /// `xcpp.toml`完整记录了项目的编译参数，即使为空也会覆盖全局配置
+    fn project(manifest: Manifest) -> Layer {
+        let non_empty = |value: String| Some(value).filter(|value| !value.is_empty());
+        let flags = manifest.flags;
+        Layer {
+            std: non_empty(manifest.std),
+            path: non_empty(manifest.toolchain),
+            template: non_empty(manifest.template),
+            warnings: Some(flags.warnings),
+            defines: Some(flags.defines),
+            include_dirs: Some(flags.include_dirs),
+            links: Some(flags.links),
+        }
+    }
+
+    /// 只有这一层时的编译参数，用于写入`config.toml`
+    pub(crate) fn into_flags(self) -> Flags {
+        Flags {
+            warnings: self.warnings.unwrap_or_default(),
+            defines: self.defines.unwrap_or_default(),
+            include_dirs: self.include_dirs.unwrap_or_default(),
+            links: self.links.unwrap_or_default(),
+        }
+    }
+
+    /// `XCPP_*`环境变量，列表以逗号分隔
+    fn env() -> anyhow::Result<Layer> {
+        let var = |key: &str| std::env::var(env_name(key)).ok();
+        let list = |key: &str| var(key).map(|value| {
+            value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(ToOwned::to_owned).collect()
+        });
+        let warnings = match var("warnings") {
+            Some(warnings) => Some(warnings.parse().with_context(|| format!("Invalid `{}`", env_name("warnings")))?),
+            None => None,
+        };
+        Ok(Layer {
+            std: var("std"),
+            path: var("path"),
+            template: var("template"),
+            warnings,
+            defines: list("defines"),
+            include_dirs: list("include_dirs"),
+            links: list("links"),
+        })
+    }
+}
+
+/// 配置项对应的环境变量
+fn env_name(key: &str) -> String {
+    format!("XCPP_{}", key.to_uppercase())
+}
+
+/// 生效的配置值及其来源
+#[derive(Debug)]
+pub(crate) struct Value<T> {
+    pub(crate) value: T,
+    pub(crate) origin: Origin,
+}
+
+impl<T> Value<T> {
+    fn new(value: T) -> Value<T> {
+        Value { value, origin: Origin::Default }
+    }
+
+    fn set(&mut self, value: Option<T>, origin: Origin) {
+        if let Some(value) = value {
+            *self = Value { value, origin };
+        }
+    }
+    /// 来自命令行或`xcpp.toml`时返回值，来自环境变量与全局配置的值不写回`xcpp.toml`
+    fn persisted(&self) -> Option<&T> {
+        matches!(self.origin, Origin::Project | Origin::Cli).then_some(&self.value)
+    }
+}
+
+/// 按内置默认值 < `config.toml` < `xcpp.toml` < `XCPP_*`环境变量 < 命令行参数合并后的配置
+#[derive(Debug)]
+pub(crate) struct Settings {
+    pub(crate) std: Value<String>,
+    /// mingw64的`bin`目录，为空表示未配置
+    pub(crate) path: Value<String>,
+    pub(crate) template: Value<String>,
+    pub(crate) warnings: Value<Warnings>,
+    pub(crate) defines: Value<Vec<String>>,
+    pub(crate) include_dirs: Value<Vec<String>>,
+    pub(crate) links: Value<Vec<String>>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            std: Value::new("c++17".to_owned()),
+            path: Value::new(String::new()),
+            template: Value::new("default".to_owned()),
+            warnings: Value::new(Warnings::default()),
+            defines: Value::new(Vec::new()),
+            include_dirs: Value::new(Vec::new()),
+            links: Value::new(Vec::new()),
+        }
+    }
+}
+
+impl Settings {
+    /// 依次应用`layers`，后面的层覆盖前面的层
+    fn merge(layers: impl IntoIterator<Item = (Origin, Layer)>) -> Settings {
+        let mut settings = Settings::default();
+        for (origin, layer) in layers {
+            settings.std.set(layer.std, origin);
+            settings.path.set(layer.path, origin);
+            settings.template.set(layer.template, origin);
+            settings.warnings.set(layer.warnings, origin);
+            settings.defines.set(layer.defines, origin);
+            settings.include_dirs.set(layer.include_dirs, origin);
+            settings.links.set(layer.links, origin);
+        }
+        settings
+    }
+
+    /// 检查来自配置文件与环境变量的值，命令行参数已经由`possible_values`校验
+    fn validate(&self) -> anyhow::Result<()> {
+        if !cpp_standards().contains(&self.std.value.as_str()) {
+            bail!("Invalid std `{}` from {}", self.std.value, self.std.origin);
+        }
+        if !cfg::TEMPLATES.iter().any(|template| template.name == self.template.value) {
+            bail!("Invalid template `{}` from {}", self.template.value, self.template.origin);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn toolchain(&self) -> PathBuf {
+        PathBuf::from(&self.path.value)
+    }
+
+    pub(crate) fn template(&self) -> &'static cfg::ProjectTemplate {
+        // 经过`validate`校验，一定能找到
+        cfg::TEMPLATES.iter().find(|template| template.name == self.template.value).unwrap()
+    }
+
+    pub(crate) fn flags(&self) -> Flags {
+        Flags {
+            warnings: self.warnings.value,
+            defines: self.defines.value.clone(),
+            include_dirs: self.include_dirs.value.clone(),
+            links: self.links.value.clone(),
+        }
+    }
+
+    /// 把来自命令行或`xcpp.toml`的配置写入`manifest`，来自环境变量与全局配置的不写入
+    pub(crate) fn persist(&self, manifest: &mut Manifest) {
+        if let Some(std) = self.std.persisted() {
+            manifest.std = std.clone();
+        }
+        if let Some(path) = self.path.persisted() {
+            manifest.toolchain = path.replace('\\', "/");
+        }
+        if let Some(&warnings) = self.warnings.persisted() {
+            manifest.flags.warnings = warnings;
+        }
+        if let Some(defines) = self.defines.persisted() {
+            manifest.flags.defines = defines.clone();
+        }
+        if let Some(include_dirs) = self.include_dirs.persisted() {
+            manifest.flags.include_dirs = include_dirs.clone();
+        }
+        if let Some(links) = self.links.persisted() {
+            manifest.flags.links = links.clone();
+        }
+    }
+
+    /// 各配置项的名称、TOML格式的值与来源
+    pub(crate) fn entries(&self) -> Vec<(&'static str, String, Origin)> {
+        vec![
+            ("std", format!("{:?}", self.std.value), self.std.origin),
+            ("path", format!("{:?}", self.path.value), self.path.origin),
+            ("template", format!("{:?}", self.template.value), self.template.origin),
+            ("warnings", format!("{:?}", self.warnings.value.name()), self.warnings.origin),
+            ("defines", format!("{:?}", self.defines.value), self.defines.origin),
+            ("include_dirs", format!("{:?}", self.include_dirs.value), self.include_dirs.origin),
+            ("links", format!("{:?}", self.links.value), self.links.origin),
+        ]
+    }
+}
+
+/// 读取各层配置并合并，`project`为`dir`下的`xcpp.toml`是否参与合并
+pub(crate) fn resolve(cli: Layer, dir: &Path, project: bool) -> anyhow::Result<Settings> {
+    let global = load().map_err(|e| XcppError::Config(format!("{e:#}")))?;
+    let mut layers = vec![(Origin::Global, Layer::global(global))];
+    if project {
+        if let Some(manifest) = Manifest::load(dir)? {
+            layers.push((Origin::Project, Layer::project(manifest)));
+        }
+    }
+    layers.push((Origin::Env, Layer::env().map_err(|e| XcppError::Config(format!("{e:#}")))?));
+    layers.push((Origin::Cli, cli));
+    let settings = Settings::merge(layers);
+    settings.validate().map_err(|e| XcppError::Config(e.to_string()))?;
+    Ok(settings)
+}
+
+/// 打印生效的配置，`origin`为真时注明每一项的来源
+pub(crate) fn show(settings: &Settings, origin: bool) {
+    let entries = settings.entries();
+    let width = entries.iter().map(|(key, value, _)| key.len() + value.len() + 3).max().unwrap_or_default();
+    for (key, value, source) in entries {
+        let line = format!("{key} = {value}");
+        match (origin, source) {
+            (false, _) => report!("{line}"),
+            (true, Origin::Env) => report!("{line:<width$}  # {source} `{}`", env_name(key)),
+            (true, _) => report!("{line:<width$}  # {source}"),
+        }
+    }
+}
+
+// 需要工具链的命令共用的参数
+#[derive(StructOpt)]
+pub(crate) struct ToolchainArgs {
+    /// Cpp standard, will be passed as `--std=<std>` when compile .cpp files.
+    #[structopt(long, possible_values = &cpp_standards())]
+    std: Option<String>,
+    /// The path of mingw64, e.g. `E:/Environment/mingw64_14_2_0/bin` in my Windows OS.
+    #[structopt(long)]
+    path: Option<String>,
+}
+
+impl ToolchainArgs {
+    /// 在`base`上加入命令行给出的std与mingw64路径
+    pub(crate) fn layer(self, base: Layer) -> Layer {
+        Layer { std: self.std, path: self.path, ..base }
+    }
+}
+
+/// `xcpp config`的子命令
+#[derive(StructOpt)]
+pub(crate) enum ConfigCmd {
+    /// Print the effective settings in the current directory.
+    Show {
+        /// Explain where each value comes from: the default, `config.toml`, `xcpp.toml`, an `XCPP_*` environment variable or the command line.
+        #[structopt(long)]
+        origin: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_test() {
+        let global = MyConfig {
+            std: "c++14".to_owned(),
+            mingw64_path: "D:/wow/mingw64/bin".to_owned(),
+            ..Default::default()
+        };
+        let cli = Layer { std: Some("c++20".to_owned()), ..Default::default() };
+        let settings = Settings::merge([(Origin::Global, Layer::global(global)), (Origin::Cli, cli)]);
+        // cli input overwrites config
+        assert_eq!((settings.std.value.as_str(), settings.std.origin), ("c++20", Origin::Cli));
+        // uses config
+        assert_eq!((settings.path.value.as_str(), settings.path.origin), ("D:/wow/mingw64/bin", Origin::Global));
+        assert_eq!((settings.template.value.as_str(), settings.template.origin), ("default", Origin::Default));
+    }
+
+    #[test]
+    fn migrate_test() {
+        let mut table: toml::Table = "std = \"cfg\"\nmingw64_path = 'E:\\mingw64\\bin'\n".parse().unwrap();
+        assert_eq!(migrate(&mut table).unwrap(), 1);
+        let config: MyConfig = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!((config.version, config.std.as_str(), config.mingw64_path.as_str()), (CONFIG_VERSION, "", "E:/mingw64/bin"));
+
+        let mut table: toml::Table = format!("version = {}\n", CONFIG_VERSION + 1).parse().unwrap();
+        assert!(migrate(&mut table).is_err());
+    }
+
+    #[test]
+    fn project_layer_test() {
+        let global = MyConfig {
+            flags: Flags { defines: vec!["GLOBAL".to_owned()], ..Default::default() },
+            ..Default::default()
+        };
+        let manifest = Manifest { std: "c++20".to_owned(), ..Default::default() };
+        let settings = Settings::merge([(Origin::Global, Layer::global(global)), (Origin::Project, Layer::project(manifest))]);
+        assert_eq!((settings.std.value.as_str(), settings.std.origin), ("c++20", Origin::Project));
+        // 项目记录的编译参数为空时也不使用全局配置
+        assert!(settings.defines.value.is_empty());
+        assert_eq!(settings.defines.origin, Origin::Project);
+    }
+
+    #[test]
+    fn persist_test() {
+        let mut manifest = Manifest {
+            std: "c++17".to_owned(),
+            flags: Flags { defines: vec!["LOCAL".to_owned()], ..Default::default() },
+            ..Default::default()
+        };
+        let env = Layer { std: Some("c++20".to_owned()), path: Some("/opt/mingw64/bin".to_owned()), ..Default::default() };
+        let cli = Layer { links: Some(vec!["gmp".to_owned()]), ..Default::default() };
+        let settings = Settings::merge([(Origin::Project, Layer::project(manifest.clone())), (Origin::Env, env), (Origin::Cli, cli)]);
+        settings.persist(&mut manifest);
+        // 环境变量只对本次执行生效
+        assert_eq!((manifest.std.as_str(), manifest.toolchain.as_str()), ("c++17", ""));
+        assert_eq!((manifest.flags.defines, manifest.flags.links), (vec!["LOCAL".to_owned()], vec!["gmp".to_owned()]));
+    }
+}
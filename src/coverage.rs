@@ -0,0 +1,208 @@
+use std::{collections::BTreeMap, fmt::Write, fs::File, path::{Path, PathBuf}, process::{Command, Stdio}};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+
+use crate::{build, create_file, create_file_with_content, flags::Flags, judge::INTERACTOR, message::{self, report}, mkdir, pch, project::Manifest};
+
+/// 覆盖率的编译产物与报告所在目录
+const DIR: &str = "target/coverage";
+
+/// 源文件中每个可执行行的执行次数
+type Lines = BTreeMap<u32, u64>;
+
+/// 以`coverage`参数编译并运行单元测试，未配置测试框架时以`data.in`为输入运行项目，
+/// 然后用gcov统计项目源文件的行覆盖率，在`target/coverage/`中生成lcov报告
+pub(crate) fn coverage(std: &str, toolchain: &Path, flags: &Flags) -> anyhow::Result<()> {
+    let dir = std::env::current_dir().context("Failed to get current directory")?;
+    let name = dir.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid project directory `{}`", dir.display()))?;
+    let mut manifest = Manifest::load(&dir)?.unwrap_or_default();
+    manifest.flags = flags.clone();
+    let profile = build::find_profile("coverage");
+    if let Some(pch) = &manifest.pch {
+        pch::precompile(&dir, toolchain, std, &pch.headers, &manifest.flags, &[profile])?;
+    }
+
+    // 上次运行的计数会累加到.gcda中，先清理
+    let out_dir = dir.join(DIR);
+    if out_dir.exists() {
+        std::fs::remove_dir_all(&out_dir)
+            .with_context(|| format!("Failed to clean `{}`", out_dir.display()))?;
+    }
+    mkdir(&out_dir)?;
+
+    let binary = out_dir.join(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    let mut cmd = Command::new(&binary);
+    if manifest.test_framework.is_some() {
+        build::compile_tests(toolchain, std, profile, &dir, &manifest, &binary)?;
+        cmd.stdout(message::child_stdout());
+    } else {
+        let sources = build::sources(&dir, manifest.layout, manifest.kind, Path::new(INTERACTOR))?;
+        build::compile(toolchain, std, profile, &manifest, &sources, &[], &binary)?;
+        let input = dir.join("data.in");
+        cmd.stdin(File::open(&input).with_context(|| format!("Failed to open `{}`", input.display()))?)
+            .stdout(create_file(&dir.join("data.out"))?);
+    }
+    info!("Running `{}`", binary.display());
+    message::command_run(&cmd);
+    let status = cmd.status()
+        .with_context(|| format!("Failed to run `{}`", binary.display()))?;
+    if !status.success() {
+        warn!("`{}` exited with {status}, the coverage may be incomplete", binary.display());
+    }
+
+    let files = collect(toolchain, &dir, &out_dir)?;
+    print_summary(&dir, &files);
+
+    let lcov = out_dir.join("lcov.info");
+    create_file_with_content(&lcov, &to_lcov(&files))?;
+    report!("lcov report written to `{}`", lcov.display());
+    // genhtml来自lcov，不是工具链的一部分，没有安装时只生成lcov.info
+    let html = out_dir.join("html");
+    let mut cmd = Command::new("genhtml");
+    cmd.arg("--quiet").arg(&lcov).arg("-o").arg(&html).stdout(Stdio::null());
+    message::command_run(&cmd);
+    match cmd.status() {
+        Ok(status) if status.success() => report!("HTML report written to `{}`", html.join("index.html").display()),
+        Ok(status) => warn!("`genhtml` exited with {status}"),
+        Err(_) => info!("`genhtml` not found, skipping the HTML report"),
+    }
+    Ok(())
+}
+
+/// 对`out_dir`中的.gcda运行gcov，合并得到项目内各源文件的执行次数
+fn collect(toolchain: &Path, dir: &Path, out_dir: &Path) -> anyhow::Result<BTreeMap<PathBuf, Lines>> {
+    let mut gcda = Vec::new();
+    for entry in std::fs::read_dir(out_dir).with_context(|| format!("Failed to read `{}`", out_dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "gcda") {
+            gcda.push(path);
+        }
+    }
+    if gcda.is_empty() {
+        bail!("No coverage data was written, the program may have crashed");
+    }
+
+    let gcov = build::tool(toolchain, "gcov");
+    // 源文件与`-I`目录是相对项目目录记录的，因此在项目目录下运行gcov；
+    // `-t`把结果输出到标准输出，不在项目中留下.gcov文件
+    let mut cmd = Command::new(&gcov);
+    cmd.arg("-t")
+        .arg("-o")
+        .arg(out_dir)
+        .args(&gcda)
+        .current_dir(dir)
+        .stderr(Stdio::null());
+    message::command_run(&cmd);
+    let output = cmd.output()
+        .with_context(|| format!("Failed to evaluate `{}`", gcov.display()))?;
+    if !output.status.success() {
+        bail!("`{}` exited with {}", gcov.display(), output.status);
+    }
+
+    let mut files: BTreeMap<PathBuf, Lines> = BTreeMap::new();
+    for (source, lines) in parse_gcov(&String::from_utf8_lossy(&output.stdout)) {
+        // 只统计项目自己的源文件，不包括系统头文件、预编译头文件与第三方测试框架
+        let source = dir.join(source);
+        if !source.starts_with(dir) || source.starts_with(dir.join("target")) || source.starts_with(dir.join(crate::framework::VENDOR_DIR)) {
+            continue;
+        }
+        // 头文件可能被多个源文件包含，执行次数相加
+        let merged = files.entry(source).or_default();
+        for (line, count) in lines {
+            *merged.entry(line).or_default() += count;
+        }
+    }
+    Ok(files)
+}
+
+/// 解析gcov的文本输出，返回其中每个源文件的路径与各可执行行的执行次数
+fn parse_gcov(content: &str) -> Vec<(&str, Lines)> {
+    let mut files: Vec<(&str, Lines)> = Vec::new();
+    for line in content.lines() {
+        let mut parts = line.splitn(3, ':');
+        let (Some(count), Some(number), Some(text)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+        let Ok(number) = number.trim().parse::<u32>() else {
+            continue;
+        };
+        let count = count.trim();
+        if number == 0 {
+            if let Some(path) = text.strip_prefix("Source:") {
+                files.push((path, Lines::new()));
+            }
+            continue;
+        }
+        // `-`为不可执行的行，`#####`与`=====`为未执行的行，`*`标记部分基本块未执行
+        let count = match count {
+            "-" => continue,
+            "#####" | "=====" => 0,
+            count => match count.trim_end_matches('*').parse() {
+                Ok(count) => count,
+                Err(_) => continue,
+            },
+        };
+        if let Some((_, lines)) = files.last_mut() {
+            lines.insert(number, count);
+        }
+    }
+    files
+}
+
+fn print_summary(dir: &Path, files: &BTreeMap<PathBuf, Lines>) {
+    let percent = |hit: usize, total: usize| if total == 0 { 100.0 } else { hit as f64 * 100.0 / total as f64 };
+    report!("{:<40} {:>12} {:>8}", "File", "Lines", "Cover");
+    let (mut total_hit, mut total) = (0, 0);
+    for (path, lines) in files {
+        let hit = lines.values().filter(|&&count| count > 0).count();
+        total_hit += hit;
+        total += lines.len();
+        let name = path.strip_prefix(dir).unwrap_or(path).display().to_string().replace('\\', "/");
+        report!("{:<40} {:>12} {:>7.1}%", name, format!("{hit}/{}", lines.len()), percent(hit, lines.len()));
+    }
+    report!("{:<40} {:>12} {:>7.1}%", "Total", format!("{total_hit}/{total}"), percent(total_hit, total));
+}
+
+/// 生成lcov的tracefile格式
+fn to_lcov(files: &BTreeMap<PathBuf, Lines>) -> String {
+    let mut out = String::from("TN:\n");
+    for (path, lines) in files {
+        let _ = writeln!(out, "SF:{}", path.display());
+        for (line, count) in lines {
+            let _ = writeln!(out, "DA:{line},{count}");
+        }
+        let hit = lines.values().filter(|&&count| count > 0).count();
+        let _ = writeln!(out, "LF:{}\nLH:{hit}\nend_of_record", lines.len());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gcov_test() {
+        let content = "        -:    0:Source:/tmp/p/main.cpp
+        -:    0:Graph:p-main.gcno
+        -:    1:#include <cstdio>
+        1:    2:int main() {
+        1*:    3:    if (0) {
+    #####:    4:        puts(\"never\");
+        -:    5:    }
+        3:    6:    return 0;
+        -:    7:}
+        -:    0:Source:include/p/p.h
+        2:    1:inline int p() { return 1; }
+";
+        let files = parse_gcov(content);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, "/tmp/p/main.cpp");
+        assert_eq!(files[0].1.clone().into_iter().collect::<Vec<_>>(), [(2, 1), (3, 1), (4, 0), (6, 3)]);
+        assert_eq!(files[1].0, "include/p/p.h");
+        assert_eq!(files[1].1.clone().into_iter().collect::<Vec<_>>(), [(1, 2)]);
+    }
+}
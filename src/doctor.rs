@@ -0,0 +1,175 @@
+use std::{path::Path, process::Command};
+
+use anyhow::bail;
+
+use crate::{build, config::{self, Origin}, message::report, paths};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// 一项检查的结果，有问题时`fix`给出解决办法
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Check {
+        Check { name, status: Status::Ok, detail: detail.into(), fix: None }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check { name, status: Status::Warning, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn error(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check { name, status: Status::Error, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn print(&self) {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+        };
+        report!("{:<9} {:<12} {}", format!("[{status}]"), self.name, self.detail);
+        if let Some(fix) = &self.fix {
+            report!("{:<22} fix: {fix}", "");
+        }
+    }
+}
+
+/// 运行`program <arg>`，返回输出的第一行，无法运行或失败时返回`None`
+fn version(program: &Path, arg: &str) -> Option<String> {
+    let output = Command::new(program).arg(arg).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().next().unwrap_or_default().trim().to_owned())
+}
+
+/// 检查工具链中的`name`存在并且能够运行
+fn check_tool(name: &'static str, toolchain: &Path, fix: &str) -> Check {
+    let program = build::tool(toolchain, name);
+    if !program.exists() {
+        return Check::error(name, format!("`{}` not found", program.display()), fix);
+    }
+    match version(&program, "--version") {
+        Some(version) => Check::ok(name, version),
+        None => Check::error(name, format!("`{} --version` failed", program.display()), fix),
+    }
+}
+
+/// mingw-builds的g++应为64位并使用posix线程模型，否则`std::thread`等无法使用
+fn check_flavour(toolchain: &Path) -> Option<Check> {
+    let gpp = build::tool(toolchain, "g++");
+    let target = version(&gpp, "-dumpmachine")?;
+    let output = Command::new(&gpp).arg("-v").output().ok()?;
+    let verbose = String::from_utf8_lossy(&output.stderr);
+    let threads = verbose.lines()
+        .find_map(|line| line.strip_prefix("Thread model: "))
+        .unwrap_or("unknown")
+        .trim()
+        .to_owned();
+    let detail = format!("{target}, thread model {threads}");
+    let fix = "Download an `x86_64-*-release-posix-seh-ucrt` build from https://github.com/niXman/mingw-builds-binaries/releases";
+    Some(if !target.starts_with("x86_64") && !target.starts_with("aarch64") {
+        Check::warning("flavour", format!("{detail}, a 32-bit toolchain"), fix)
+    } else if cfg!(windows) && !target.contains("mingw") {
+        Check::warning("flavour", format!("{detail}, not a MinGW toolchain"), fix)
+    } else if threads == "win32" {
+        Check::warning("flavour", format!("{detail}, `std::thread` may be unavailable"), fix)
+    } else {
+        Check::ok("flavour", detail)
+    })
+}
+
+/// 检查配置、工具链、make与git，打印每一项的结果，有错误时返回`Err`
+pub(crate) fn doctor(dir: &Path) -> anyhow::Result<()> {
+    let mut checks = Vec::new();
+    let store = "Run `xcpp store --std c++17 --path <MINGW64_PATH>`";
+    let settings = match config::load() {
+        Err(e) => {
+            checks.push(Check::error("config.toml", format!("{e:#}"), "Fix or delete `config.toml`, then run `xcpp store`"));
+            None
+        }
+        Ok(global) => {
+            checks.push(Check::ok("config.toml", format!("version {}", global.version)));
+            match config::resolve(config::Layer::default(), dir, true) {
+                Ok(settings) => Some(settings),
+                Err(e) => {
+                    checks.push(Check::error("settings", format!("{e:#}"), "Correct the value, see `xcpp config show --origin`"));
+                    None
+                }
+            }
+        }
+    };
+
+    let toolchain = settings.as_ref().and_then(|settings| {
+        let path = &settings.path.value;
+        if path.is_empty() {
+            checks.push(Check::error("toolchain", "mingw64 path is not configured", format!("{store} or set `XCPP_PATH`")));
+            return None;
+        }
+        let origin = match settings.path.origin {
+            Origin::Env => "`XCPP_PATH`".to_owned(),
+            origin => origin.to_string(),
+        };
+        let toolchain = settings.toolchain();
+        if !toolchain.is_dir() {
+            checks.push(Check::error("toolchain", format!("`{path}` from {origin} doesn't exist"), format!("Install mingw64 there or correct the path in {origin}")));
+            return None;
+        }
+        match paths::problem(path) {
+            Some(problem) => checks.push(Check::warning("toolchain", format!("`{path}` {problem}"), "Move mingw64 to a path without spaces or non-ASCII characters, e.g. `C:/mingw64/bin`")),
+            None => checks.push(Check::ok("toolchain", format!("`{path}` from {origin}"))),
+        }
+        Some(toolchain)
+    });
+
+    if let Some(toolchain) = &toolchain {
+        let reinstall = "Reinstall mingw64 from https://github.com/niXman/mingw-builds-binaries/releases";
+        checks.push(check_tool("g++", toolchain, reinstall));
+        checks.extend(check_flavour(toolchain));
+        checks.push(check_tool("gdb", toolchain, format!("{reinstall}, debugging in VSCode needs gdb").as_str()));
+        // VSCode任务调用工具链中的mingw32-make，其他系统上可以使用PATH中的make
+        let make = check_tool("mingw32-make", toolchain, reinstall);
+        checks.push(match (make.status, version(Path::new("make"), "--version")) {
+            (Status::Error, Some(version)) => Check::warning(
+                "mingw32-make",
+                format!("{}, found `make` in PATH: {version}", make.detail),
+                "The VSCode tasks call mingw32-make in the toolchain, use `make` in a terminal instead",
+            ),
+            _ => make,
+        });
+    }
+
+    checks.push(match version(Path::new("git"), "--version") {
+        Some(version) => Check::ok("git", version),
+        None => Check::warning("git", "`git` not found in PATH", "Install git from https://git-scm.com and add it to PATH, `xcpp new` runs `git init`"),
+    });
+
+    let cwd = dir.to_string_lossy();
+    checks.push(match paths::problem(&cwd) {
+        Some(problem) => Check::warning("directory", format!("`{cwd}` {problem}"), "Create projects in a path without spaces or non-ASCII characters"),
+        None => Check::ok("directory", format!("`{cwd}`")),
+    });
+
+    for check in &checks {
+        check.print();
+    }
+    let errors = checks.iter().filter(|check| check.status == Status::Error).count();
+    let warnings = checks.iter().filter(|check| check.status == Status::Warning).count();
+    if errors > 0 {
+        bail!("Found {errors} errors and {warnings} warnings");
+    }
+    report!("No errors, {warnings} warnings");
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::{fmt, io, path::PathBuf};
+
+/// 可以区分的失败类别，每一类对应一个退出码，与README中的表格保持一致
+#[derive(Debug)]
+pub(crate) enum XcppError {
+    /// `config.toml`、`xcpp.toml`、`XCPP_*`环境变量或命令行参数有误，包括不可用的项目名与目标目录
+    Config(String),
+    /// mingw64未配置、不存在，或其中的工具无法运行
+    Toolchain(String),
+    /// 读写文件或目录失败
+    Filesystem { action: &'static str, path: PathBuf, source: io::Error },
+    /// 模板、测试框架的头文件缺失，或生成的文件无效
+    Template(String),
+    /// git执行失败
+    Vcs(String),
+}
+
+impl XcppError {
+    pub(crate) fn filesystem(action: &'static str, path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> XcppError {
+        let path = path.into();
+        move |source| XcppError::Filesystem { action, path, source }
+    }
+
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            XcppError::Config(_) => 2,
+            XcppError::Toolchain(_) => 3,
+            XcppError::Filesystem { .. } => 4,
+            XcppError::Template(_) => 5,
+            XcppError::Vcs(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for XcppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XcppError::Config(message) | XcppError::Toolchain(message) | XcppError::Template(message) | XcppError::Vcs(message) => {
+                f.write_str(message)
+            }
+            XcppError::Filesystem { action, path, source } => write!(f, "Failed to {action} `{}`: {source}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for XcppError {}
+
+/// 错误对应的退出码，不属于以上类别的失败（如编译错误、测试未通过）为1
+pub(crate) fn exit_code(error: &anyhow::Error) -> i32 {
+    error.chain()
+        .find_map(|cause| cause.downcast_ref::<XcppError>())
+        .map_or(1, XcppError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn exit_code_test() {
+        let error = anyhow::Error::from(XcppError::Toolchain("`g++` not found".to_owned())).context("Failed to build");
+        assert_eq!(exit_code(&error), 3);
+        let source = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let error = Err::<(), _>(XcppError::filesystem("create", "a/b")(source)).context("Failed to create project").unwrap_err();
+        assert_eq!(exit_code(&error), 4);
+        assert_eq!(format!("{error:#}"), "Failed to create project: Failed to create `a/b`: denied");
+        assert_eq!(exit_code(&anyhow::anyhow!("Tests failed")), 1);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{collections::HashMap, path::Path};
+
+use text_placeholder::Template;
+
+use crate::error::XcppError;
+
+/// 模板所在文件的格式，决定填入的值如何转义
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Makefile,
+    /// 源文件等，原样填入
+    Text,
+}
+
+impl Format {
+    /// 按文件名判断格式
+    pub(crate) fn of(path: &str) -> Format {
+        if path.ends_with(".json") {
+            Format::Json
+        } else if path.rsplit('/').next() == Some("makefile") {
+            Format::Makefile
+        } else {
+            Format::Text
+        }
+    }
+
+    pub(crate) fn escape(self, value: &str) -> String {
+        match self {
+            Format::Json => json(value),
+            Format::Makefile => makefile(value),
+            Format::Text => value.to_owned(),
+        }
+    }
+}
+
+/// 转义为JSON字符串的内容，不含两侧的引号
+pub(crate) fn json(value: &str) -> String {
+    let quoted = serde_json::to_string(value).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_owned()
+}
+
+/// 转义makefile中的值，`$`会被当作变量引用，`#`会被当作注释
+pub(crate) fn makefile(value: &str) -> String {
+    value.replace('$', "$$").replace('#', "\\#")
+}
+
+/// 按`format`转义`table`中的值后填充`template`，`fragments`中的值是已经按该格式生成的片段，原样填入
+pub(crate) fn fill(template: &str, table: &HashMap<&str, &str>, format: Format, fragments: &[&str]) -> String {
+    let escaped: HashMap<&str, String> = table.iter()
+        .map(|(&key, &value)| (key, if fragments.contains(&key) { value.to_owned() } else { format.escape(value) }))
+        .collect();
+    let escaped: HashMap<&str, &str> = escaped.iter().map(|(&key, value)| (key, value.as_str())).collect();
+    Template::new(template).fill_with_hashmap(&escaped)
+}
+
+/// 写入前检查生成的JSON能否解析，失败说明模板或转义有误
+pub(crate) fn validate(path: &Path, content: &str, format: Format) -> anyhow::Result<()> {
+    if format == Format::Json {
+        serde_json::from_str::<serde_json::Value>(content)
+            .map_err(|e| XcppError::Template(format!("Generated invalid JSON for `{}`: {e}", path.display())))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_test() {
+        let table = HashMap::from([("gdb", r#"C:\my "tools"\gdb.exe"#), ("args", "\"-O2\",")]);
+        let content = fill(r#"{"gdb": "{{gdb}}", "args": [{{args}} "-g"]}"#, &table, Format::Json, &["args"]);
+        assert_eq!(content, r#"{"gdb": "C:\\my \"tools\"\\gdb.exe", "args": ["-O2", "-g"]}"#);
+        assert!(validate(Path::new("launch.json"), &content, Format::Json).is_ok());
+        assert!(validate(Path::new("launch.json"), "{\"a\": 1,}", Format::Json).is_err());
+        assert_eq!(Format::of("makefile").escape("N=$(X)#1"), "N=$$(X)\\#1");
+    }
+}
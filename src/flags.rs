@@ -0,0 +1,160 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::bail;
+use serde_derive::{Serialize, Deserialize};
+use structopt::StructOpt;
+
+use crate::{cfg::Profile, config::Layer};
+
+/// 警告等级，替换各组编译参数中的`-Wall`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Warnings {
+    None,
+    #[default]
+    Default,
+    Strict,
+    Pedantic,
+}
+
+impl Warnings {
+    pub(crate) const NAMES: [&'static str; 4] = ["none", "default", "strict", "pedantic"];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Warnings::None => "none",
+            Warnings::Default => "default",
+            Warnings::Strict => "strict",
+            Warnings::Pedantic => "pedantic",
+        }
+    }
+
+    pub(crate) fn flags(self) -> &'static [&'static str] {
+        match self {
+            Warnings::None => &[],
+            Warnings::Default => &["-Wall"],
+            Warnings::Strict => &["-Wall", "-Wextra", "-Wshadow", "-Wconversion"],
+            Warnings::Pedantic => &["-Wall", "-Wextra", "-Wshadow", "-Wconversion", "-Wpedantic"],
+        }
+    }
+}
+
+impl FromStr for Warnings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Warnings::None),
+            "default" => Ok(Warnings::Default),
+            "strict" => Ok(Warnings::Strict),
+            "pedantic" => Ok(Warnings::Pedantic),
+            _ => bail!("Invalid warning level `{s}`"),
+        }
+    }
+}
+
+/// 用户追加的编译参数，对应`xcpp.toml`与`config.toml`中的`[flags]`
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Flags {
+    #[serde(default)]
+    pub(crate) warnings: Warnings,
+    /// `-D`定义的宏，形如`NAME`或`NAME=VALUE`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) defines: Vec<String>,
+    /// `-I`目录，相对路径以项目根目录为基准
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) include_dirs: Vec<String>,
+    /// 以`-l`链接的库
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) links: Vec<String>,
+}
+
+impl Flags {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Flags::default()
+    }
+
+    /// `profile`的编译参数，其中的`-Wall`替换为警告等级对应的参数，并追加宏定义
+    pub(crate) fn compile(&self, profile: &Profile) -> Vec<String> {
+        let mut flags = Vec::new();
+        for &flag in profile.flags {
+            match flag {
+                "-Wall" => flags.extend(self.warnings.flags().iter().map(|flag| flag.to_string())),
+                _ => flags.push(flag.to_owned()),
+            }
+        }
+        flags.extend(self.defines.iter().map(|define| format!("-D{define}")));
+        flags
+    }
+
+    /// 头文件目录，相对路径拼接在`root`之后
+    pub(crate) fn include_paths(&self, root: &str) -> Vec<String> {
+        self.include_dirs.iter()
+            .map(|dir| if root.is_empty() || Path::new(dir).is_absolute() {
+                dir.clone()
+            } else {
+                format!("{root}/{dir}")
+            })
+            .collect()
+    }
+
+    pub(crate) fn includes(&self, root: &str) -> Vec<String> {
+        self.include_paths(root).iter().map(|dir| format!("-I{dir}")).collect()
+    }
+
+    /// 链接参数，放在源文件之后
+    pub(crate) fn links(&self) -> Vec<String> {
+        self.links.iter().map(|lib| format!("-l{lib}")).collect()
+    }
+}
+
+// 设置编译参数的命令共用的参数
+#[derive(StructOpt)]
+pub(crate) struct FlagArgs {
+    /// Warnings to enable: `default` is `-Wall`, `strict` adds `-Wextra -Wshadow -Wconversion` and `pedantic` adds `-Wpedantic`.
+    /// Defaults to `XCPP_WARNINGS`, then `warnings` in `xcpp.toml` or `config.toml`.
+    #[structopt(long, possible_values = &Warnings::NAMES)]
+    warnings: Option<Warnings>,
+    /// Define a macro for every build, e.g. `--define N=100`.
+    #[structopt(long = "define", number_of_values = 1)]
+    defines: Vec<String>,
+    /// Add a directory to the include path of every build.
+    #[structopt(long = "include-dir", number_of_values = 1)]
+    include_dirs: Vec<String>,
+    /// Link a library to every build, e.g. `--link pthread` for `-lpthread`.
+    #[structopt(long = "link", number_of_values = 1)]
+    links: Vec<String>,
+}
+
+impl FlagArgs {
+    /// 命令行给出的参数作为一层配置，未给出的列表不覆盖下层
+    pub(crate) fn layer(self) -> Layer {
+        let non_empty = |list: Vec<String>| Some(list).filter(|list| !list.is_empty());
+        Layer {
+            warnings: self.warnings,
+            defines: non_empty(self.defines),
+            include_dirs: non_empty(self.include_dirs),
+            links: non_empty(self.links),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_test() {
+        let flags = Flags {
+            warnings: Warnings::Strict,
+            defines: vec!["N=100".to_owned()],
+            include_dirs: vec!["third_party".to_owned()],
+            links: vec!["pthread".to_owned()],
+        };
+        let profile = Profile { name: "O2", label: "O2", flags: &["-g", "-Wall", "-O2"] };
+        assert_eq!(flags.compile(&profile), ["-g", "-Wall", "-Wextra", "-Wshadow", "-Wconversion", "-O2", "-DN=100"]);
+        assert_eq!(flags.includes("${workspaceFolder}"), ["-I${workspaceFolder}/third_party"]);
+        assert_eq!(flags.links(), ["-lpthread"]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::bail;
+use log::info;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::{cfg::{self, ConfigFile}, config_dir, error::XcppError, plan::Output};
+
+/// 单元测试框架的头文件放在项目中的位置，编译测试时以`-I`引用
+pub(crate) const VENDOR_DIR: &str = "tests/vendor";
+
+/// 单元测试框架
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TestFramework {
+    Doctest,
+    Catch2,
+    Gtest,
+}
+
+impl TestFramework {
+    pub(crate) const NAMES: [&'static str; 3] = ["doctest", "catch2", "gtest"];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            TestFramework::Doctest => "doctest",
+            TestFramework::Catch2 => "catch2",
+            TestFramework::Gtest => "gtest",
+        }
+    }
+
+    /// 单头文件的框架需要复制到项目中的头文件，gtest需要工具链中已经安装
+    pub(crate) fn header(self) -> Option<&'static str> {
+        match self {
+            TestFramework::Doctest => Some("doctest.h"),
+            TestFramework::Catch2 => Some("catch.hpp"),
+            TestFramework::Gtest => None,
+        }
+    }
+
+    /// 链接测试程序时额外的参数
+    pub(crate) fn libs(self) -> &'static [&'static str] {
+        match self {
+            TestFramework::Gtest => &["-lgtest_main", "-lgtest", "-pthread"],
+            _ => &[],
+        }
+    }
+
+    /// 提供main函数的测试文件，gtest链接`gtest_main`即可
+    pub(crate) fn main(self) -> Option<ConfigFile> {
+        match self {
+            TestFramework::Doctest => Some(cfg::DOCTEST_MAIN),
+            TestFramework::Catch2 => Some(cfg::CATCH2_MAIN),
+            TestFramework::Gtest => None,
+        }
+    }
+
+    /// `xcpp add test`使用的模板名，见`cfg::ITEMS`
+    pub(crate) fn item(self) -> &'static str {
+        match self {
+            TestFramework::Doctest => "doctest_test.cpp",
+            TestFramework::Catch2 => "catch2_test.cpp",
+            TestFramework::Gtest => "gtest_test.cpp",
+        }
+    }
+}
+
+impl FromStr for TestFramework {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doctest" => Ok(TestFramework::Doctest),
+            "catch2" => Ok(TestFramework::Catch2),
+            "gtest" => Ok(TestFramework::Gtest),
+            _ => bail!("Invalid test framework `{s}`"),
+        }
+    }
+}
+
+/// 把框架的头文件从本地缓存复制到`work_path`下的`VENDOR_DIR`，不访问网络。
+/// 本地缓存为全局配置目录下的`vendor/`，需要事先把头文件下载到其中
+pub(crate) fn vendor(output: &mut Output, work_path: &Path, framework: TestFramework) -> anyhow::Result<()> {
+    let Some(header) = framework.header() else {
+        info!("`{}` is not vendored, make sure it's installed in the toolchain", framework.name());
+        return Ok(());
+    };
+    let cached = config_dir()?.join("vendor").join(header);
+    if !cached.is_file() {
+        return Err(XcppError::Template(format!(
+            "`{}` is not in the local cache, download the single header of {} to `{}` first",
+            header,
+            framework.name(),
+            cached.display()
+        )).into());
+    }
+    output.copy(&cached, &work_path.join(VENDOR_DIR).join(header))
+}
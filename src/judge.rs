@@ -0,0 +1,376 @@
+use std::{fmt, fs::File, io::{Read, Write}, path::{Path, PathBuf}, process::{Command, Stdio}, sync::{Arc, Mutex}, thread::{self, JoinHandle}, time::Duration};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use structopt::StructOpt;
+
+use crate::{build, create_file, error::XcppError, flags::Flags, limit::{self, Exceeded, Limits, Usage}, memcheck, message::{self, report}, mkdir, pch, project::Manifest};
+
+/// 交互题默认的交互器源文件
+pub(crate) const INTERACTOR: &str = "interactor.cpp";
+
+/// `run`与`test`共用的参数
+#[derive(StructOpt)]
+pub(crate) struct Options {
+    /// The interactor of an interactive problem, defaults to `interactor.cpp` if `xcpp.toml` marks the problem as interactive.
+    #[structopt(long, parse(from_os_str))]
+    interactor: Option<PathBuf>,
+    /// Time limit in milliseconds, defaults to `time_limit` in `xcpp.toml`.
+    #[structopt(long)]
+    time_limit: Option<u64>,
+    /// Memory limit in megabytes, defaults to `memory_limit` in `xcpp.toml`.
+    #[structopt(long)]
+    memory_limit: Option<u64>,
+}
+
+/// 单个测试点的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Accepted,
+    WrongAnswer,
+    PresentationError,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    RuntimeError,
+    /// 交互器自身出错，参考testlib的`_fail`
+    Failed,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Verdict::Accepted => "AC",
+            Verdict::WrongAnswer => "WA",
+            Verdict::PresentationError => "PE",
+            Verdict::TimeLimitExceeded => "TLE",
+            Verdict::MemoryLimitExceeded => "MLE",
+            Verdict::RuntimeError => "RE",
+            Verdict::Failed => "FAIL",
+        })
+    }
+}
+
+/// 编译好的解答与交互器，以及运行解答时的资源限制
+struct Binaries {
+    solution: PathBuf,
+    interactor: Option<PathBuf>,
+    limits: Limits,
+}
+
+/// 编译当前目录下的项目，未指定的选项按`xcpp.toml`中的题目信息补全
+fn prepare(std: &str, toolchain: &Path, flags: &Flags, options: Options) -> anyhow::Result<Binaries> {
+    let dir = std::env::current_dir().context("Failed to get current directory")?;
+    let name = dir.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid project directory `{}`", dir.display()))?;
+
+    let mut manifest = Manifest::load(&dir)?.unwrap_or_default();
+    manifest.flags = flags.clone();
+    if let Some(pch) = &manifest.pch {
+        pch::precompile(&dir, toolchain, std, &pch.headers, &manifest.flags, &[build::profile()])?;
+    }
+    let problem = manifest.problem.take().unwrap_or_default();
+    let interactor = match options.interactor {
+        Some(interactor) => Some(interactor),
+        None if problem.interactive => Some(PathBuf::from(INTERACTOR)),
+        None => None,
+    };
+    // `xcpp.toml`中为0表示不限制
+    let limits = Limits {
+        time: options.time_limit
+            .or(Some(problem.time_limit).filter(|&ms| ms > 0))
+            .map(Duration::from_millis),
+        memory: options.memory_limit
+            .or(Some(problem.memory_limit).filter(|&mb| mb > 0)),
+    };
+    info!("Limits: {limits:?}");
+
+    let exe = std::env::consts::EXE_SUFFIX;
+    let solution = dir.join("target").join(format!("{name}{exe}"));
+    build::compile(toolchain, std, build::profile(), &manifest, &build::sources(&dir, manifest.layout, manifest.kind, interactor.as_deref().unwrap_or(Path::new(INTERACTOR)))?, &[], &solution)?;
+
+    let interactor = match interactor {
+        Some(source) => {
+            if !source.exists() {
+                bail!("Interactor `{}` doesn't exist", source.display());
+            }
+            let binary = dir.join("target").join(format!("interactor{exe}"));
+            build::compile(toolchain, std, build::profile(), &manifest, &[source], &[], &binary)?;
+            Some(binary)
+        }
+        None => None,
+    };
+    Ok(Binaries { solution, interactor, limits })
+}
+
+/// 根据资源占用判断是否TLE、MLE或RE，均未发生时返回`None`
+fn check_usage(usage: &Usage, limits: &Limits) -> Option<Verdict> {
+    match usage.killed {
+        Some(Exceeded::Time) => return Some(Verdict::TimeLimitExceeded),
+        Some(Exceeded::Memory) => return Some(Verdict::MemoryLimitExceeded),
+        None => {}
+    }
+    // 无法测量CPU时间时以墙钟时间为准
+    let time = usage.cpu.unwrap_or(usage.wall);
+    if limits.time.is_some_and(|limit| time > limit) {
+        return Some(Verdict::TimeLimitExceeded);
+    }
+    if limits.memory.is_some_and(|mb| usage.memory.is_some_and(|kb| kb > mb * 1024)) {
+        return Some(Verdict::MemoryLimitExceeded);
+    }
+    if !usage.status.success() {
+        return Some(Verdict::RuntimeError);
+    }
+    None
+}
+
+/// 以`input`为输入、`output`为输出运行解答
+fn execute(solution: &Path, input: &Path, output: &Path, limits: Limits) -> anyhow::Result<Usage> {
+    let mut cmd = Command::new(solution);
+    cmd.stdin(File::open(input).with_context(|| format!("Failed to open `{}`", input.display()))?)
+        .stdout(create_file(output)?);
+    message::command_run(&cmd);
+    limit::spawn(&mut cmd, limits)
+        .and_then(|process| process.wait())
+        .with_context(|| format!("Failed to run `{}`", solution.display()))
+}
+
+/// 以`data.in`为输入运行项目，输出写入`data.out`，`memcheck`为真时改为在valgrind下运行
+pub(crate) fn run(std: &str, toolchain: &Path, flags: &Flags, options: Options, memcheck: bool) -> anyhow::Result<()> {
+    let binaries = prepare(std, toolchain, flags, options)?;
+    let input = Path::new("data.in");
+    let output = Path::new("data.out");
+
+    if memcheck {
+        if binaries.interactor.is_some() {
+            return Err(XcppError::Config("`--memcheck` doesn't support interactive problems".to_owned()).into());
+        }
+        if memcheck::available() {
+            return memcheck::run(&binaries.solution, input, output);
+        }
+        report!("`valgrind` not found, running without memcheck");
+    }
+
+    let (verdict, usage) = if let Some(interactor) = &binaries.interactor {
+        let transcript = Path::new("target/transcript.log");
+        let (verdict, usage) = interact(&binaries, interactor, input, output, None, transcript)?;
+        info!("Transcript written to `{}`", transcript.display());
+        (Some(verdict).filter(|&verdict| verdict != Verdict::Accepted), usage)
+    } else {
+        let usage = execute(&binaries.solution, input, output, binaries.limits)?;
+        (check_usage(&usage, &binaries.limits), usage)
+    };
+    report!(
+        "Finished in {} (wall {} ms), peak memory {}",
+        format_cpu(&usage), usage.wall.as_millis(), format_memory(&usage)
+    );
+    if let Some(verdict) = verdict {
+        bail!("{verdict}: program exited with {}", usage.status);
+    }
+    Ok(())
+}
+
+/// 依次运行`tests/`下的测试点，并打印结果汇总
+pub(crate) fn test(std: &str, toolchain: &Path, flags: &Flags, options: Options) -> anyhow::Result<()> {
+    let binaries = prepare(std, toolchain, flags, options)?;
+    let tests = find_tests(Path::new("tests"))?;
+    if tests.is_empty() {
+        bail!("No tests found in `tests/`");
+    }
+    let report = Path::new("target/tests");
+    mkdir(report)?;
+
+    let mut results = Vec::new();
+    for (name, input) in tests {
+        let answer = input.with_extension("ans");
+        let output = report.join(format!("{name}.out"));
+        let (verdict, usage) = if let Some(interactor) = &binaries.interactor {
+            let answer = answer.exists().then_some(answer.as_path());
+            let transcript = report.join(format!("{name}.transcript"));
+            interact(&binaries, interactor, &input, &output, answer, &transcript)?
+        } else {
+            if !answer.exists() {
+                warn!("Skipping test `{name}`: `{}` doesn't exist", answer.display());
+                continue;
+            }
+            let usage = execute(&binaries.solution, &input, &output, binaries.limits)?;
+            let verdict = match check_usage(&usage, &binaries.limits) {
+                Some(verdict) => verdict,
+                None if same_tokens(&read_lossy(&output)?, &read_lossy(&answer)?) => Verdict::Accepted,
+                None => Verdict::WrongAnswer,
+            };
+            (verdict, usage)
+        };
+        results.push((name, verdict, usage));
+    }
+
+    print_summary(&results);
+    let failed = results.iter().filter(|(_, verdict, _)| *verdict != Verdict::Accepted).count();
+    if failed > 0 {
+        bail!("{failed} of {} tests failed", results.len());
+    }
+    Ok(())
+}
+
+fn format_cpu(usage: &Usage) -> String {
+    match usage.cpu {
+        Some(cpu) => format!("{} ms", cpu.as_millis()),
+        None => "-".to_owned(),
+    }
+}
+
+fn format_memory(usage: &Usage) -> String {
+    match usage.memory {
+        Some(kb) => format!("{:.1} MB", kb as f64 / 1024.0),
+        None => "-".to_owned(),
+    }
+}
+
+fn print_summary(results: &[(String, Verdict, Usage)]) {
+    report!("{:<12} {:<8} {:>10} {:>10} {:>10}", "Test", "Verdict", "CPU", "Wall", "Memory");
+    for (name, verdict, usage) in results {
+        report!(
+            "{:<12} {:<8} {:>10} {:>10} {:>10}",
+            name, verdict, format_cpu(usage), format!("{} ms", usage.wall.as_millis()), format_memory(usage)
+        );
+    }
+    let passed = results.iter().filter(|(_, verdict, _)| *verdict == Verdict::Accepted).count();
+    report!("{passed}/{} passed", results.len());
+}
+
+/// 查找`dir`下的`*.in`，按编号排序，返回(测试点名, 输入文件)
+fn find_tests(dir: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut tests = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read `{}`", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "in") {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                tests.push((name.to_owned(), path.clone()));
+            }
+        }
+    }
+    // 数字编号按数值排序，使`10.in`排在`2.in`之后
+    tests.sort_by_key(|(name, _)| (name.parse::<u64>().unwrap_or(u64::MAX), name.clone()));
+    Ok(tests)
+}
+
+fn read_lossy(path: &Path) -> anyhow::Result<String> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read `{}`", path.display()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// 忽略空白字符差异，逐个比较token
+fn same_tokens(output: &str, answer: &str) -> bool {
+    output.split_whitespace().eq(answer.split_whitespace())
+}
+
+/// 把交互器与解答的标准输入输出互相连接，并记录双向的交互过程。
+/// 交互器按testlib的约定调用：`interactor <input> <output> [<answer>]`，
+/// 其退出码即评测结果：0为AC，1为WA，2为PE，其余视为交互器出错。
+/// 资源限制只作用于解答
+fn interact(binaries: &Binaries, interactor: &Path, input: &Path, output: &Path, answer: Option<&Path>, transcript: &Path) -> anyhow::Result<(Verdict, Usage)> {
+    let mut cmd = Command::new(interactor);
+    cmd.arg(input)
+        .arg(output)
+        .args(answer)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    message::command_run(&cmd);
+    let mut judge = cmd.spawn()
+        .with_context(|| format!("Failed to run `{}`", interactor.display()))?;
+    let mut cmd = Command::new(&binaries.solution);
+    cmd.stdin(Stdio::piped()).stdout(Stdio::piped());
+    message::command_run(&cmd);
+    let mut program = match limit::spawn(&mut cmd, binaries.limits) {
+        Ok(program) => program,
+        Err(e) => {
+            let _ = judge.kill();
+            return Err(e).with_context(|| format!("Failed to run `{}`", binaries.solution.display()));
+        }
+    };
+
+    let log = Arc::new(Mutex::new(Transcript { file: create_file(transcript)?, open: None }));
+    let relays = [
+        relay(program.child.stdout.take().unwrap(), judge.stdin.take().unwrap(), "> ", log.clone()),
+        relay(judge.stdout.take().unwrap(), program.child.stdin.take().unwrap(), "< ", log),
+    ];
+
+    let usage = program.wait().context("Failed to wait for the solution")?;
+    let judge_status = judge.wait().context("Failed to wait for the interactor")?;
+    for handle in relays {
+        let _ = handle.join();
+    }
+
+    // 解答超出限制时，交互器给出的结果没有意义
+    let verdict = match check_usage(&usage, &binaries.limits) {
+        Some(verdict @ (Verdict::TimeLimitExceeded | Verdict::MemoryLimitExceeded)) => verdict,
+        runtime_error => match judge_status.code() {
+            Some(0) => runtime_error.unwrap_or(Verdict::Accepted),
+            Some(1) => Verdict::WrongAnswer,
+            Some(2) => Verdict::PresentationError,
+            _ => Verdict::Failed,
+        },
+    };
+    Ok((verdict, usage))
+}
+
+/// 交互记录，`> `为解答发给交互器的内容，`< `为交互器发给解答的内容
+struct Transcript {
+    file: File,
+    /// 尚未换行的一行属于哪个方向
+    open: Option<&'static str>,
+}
+
+impl Transcript {
+    fn record(&mut self, prefix: &'static str, data: &[u8]) -> std::io::Result<()> {
+        for line in data.split_inclusive(|&b| b == b'\n') {
+            if self.open != Some(prefix) {
+                if self.open.is_some() {
+                    self.file.write_all(b"\n")?;
+                }
+                self.file.write_all(prefix.as_bytes())?;
+            }
+            self.file.write_all(line)?;
+            self.open = if line.ends_with(b"\n") { None } else { Some(prefix) };
+        }
+        Ok(())
+    }
+}
+
+/// 把`from`读到的内容转发给`to`并写入交互记录；`to`关闭后继续读取，避免对方阻塞
+fn relay(mut from: impl Read + Send + 'static, to: impl Write + Send + 'static, prefix: &'static str, log: Arc<Mutex<Transcript>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut to = Some(to);
+        let mut buf = [0; 4096];
+        loop {
+            let n = match from.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if let Some(writer) = &mut to {
+                if writer.write_all(&buf[..n]).and_then(|_| writer.flush()).is_err() {
+                    to = None;
+                }
+            }
+            if let Err(e) = log.lock().unwrap().record(prefix, &buf[..n]) {
+                warn!("Failed to write the transcript: {e}");
+            }
+        }
+        // drop `to` 以关闭管道，使对方读到EOF
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_tokens_test() {
+        assert!(same_tokens("1 2\n3\n", "1 2 3"));
+        assert!(same_tokens("YES\r\n", "YES\n"));
+        assert!(!same_tokens("1 2", "1 2 3"));
+    }
+}
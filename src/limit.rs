@@ -0,0 +1,184 @@
+use std::{io, process::{Child, Command, ExitStatus}, time::{Duration, Instant}};
+
+/// 轮询子进程状态的间隔
+const POLL: Duration = Duration::from_millis(5);
+
+/// 运行时的资源限制，`None`表示不限制
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Limits {
+    pub(crate) time: Option<Duration>,
+    /// 单位为MB
+    pub(crate) memory: Option<u64>,
+}
+
+impl Limits {
+    /// 超过该墙钟时间仍未结束的进程会被强制终止，用于处理阻塞、休眠等不占用CPU的情况
+    fn wall_clock(&self) -> Option<Duration> {
+        self.time.map(|time| time * 2 + Duration::from_secs(1))
+    }
+}
+
+/// 进程因超出限制而被终止的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Exceeded {
+    Time,
+    Memory,
+}
+
+/// 一次运行的结果与资源占用，无法测量的项为`None`
+#[derive(Debug)]
+pub(crate) struct Usage {
+    pub(crate) status: ExitStatus,
+    pub(crate) wall: Duration,
+    pub(crate) cpu: Option<Duration>,
+    /// 峰值RSS，单位为KB
+    pub(crate) memory: Option<u64>,
+    pub(crate) killed: Option<Exceeded>,
+}
+
+/// 在资源限制下运行的子进程
+pub(crate) struct Limited {
+    pub(crate) child: Child,
+    start: Instant,
+    limits: Limits,
+}
+
+/// 按`limits`启动`cmd`
+pub(crate) fn spawn(cmd: &mut Command, limits: Limits) -> io::Result<Limited> {
+    imp::apply(cmd, &limits);
+    let start = Instant::now();
+    let child = cmd.spawn()?;
+    Ok(Limited { child, start, limits })
+}
+
+impl Limited {
+    /// 等待进程结束，超出限制时终止进程
+    pub(crate) fn wait(mut self) -> io::Result<Usage> {
+        imp::wait(&mut self.child, self.start, &self.limits)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::{io, os::unix::process::{CommandExt, ExitStatusExt}, process::{Child, Command, ExitStatus}, thread, time::{Duration, Instant}};
+
+    use super::{Exceeded, Limits, Usage, POLL};
+
+    /// 通过rlimit限制CPU时间；地址空间只限制为内存限制的两倍作为兜底，内存超限以RSS为准
+    pub(super) fn apply(cmd: &mut Command, limits: &Limits) {
+        let cpu = limits.time.map(|time| time.as_secs() + 1);
+        let memory = limits.memory.map(|mb| mb * 2 * 1024 * 1024);
+        if cpu.is_none() && memory.is_none() {
+            return;
+        }
+        // pre_exec中只调用async-signal-safe的setrlimit
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(cpu) = cpu {
+                    let limit = libc::rlimit { rlim_cur: cpu, rlim_max: cpu + 1 };
+                    if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(memory) = memory {
+                    let limit = libc::rlimit { rlim_cur: memory, rlim_max: memory };
+                    if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// 当前RSS，单位为KB
+    fn rss(pid: libc::pid_t) -> Option<u64> {
+        let statm = std::fs::read_to_string(format!("/proc/{pid}/statm")).ok()?;
+        let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        Some(pages * page_size / 1024)
+    }
+
+    fn duration(time: libc::timeval) -> Duration {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    }
+
+    /// 用`wait4`回收子进程以获得CPU时间与峰值RSS
+    pub(super) fn wait(child: &mut Child, start: Instant, limits: &Limits) -> io::Result<Usage> {
+        let pid = child.id() as libc::pid_t;
+        let mut killed = None;
+        loop {
+            let mut status = 0;
+            let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+            let flags = if killed.is_some() { 0 } else { libc::WNOHANG };
+            let ret = unsafe { libc::wait4(pid, &mut status, flags, &mut usage) };
+            if ret == pid {
+                return Ok(Usage {
+                    status: ExitStatus::from_raw(status),
+                    wall: start.elapsed(),
+                    cpu: Some(duration(usage.ru_utime) + duration(usage.ru_stime)),
+                    memory: Some(usage.ru_maxrss as u64),
+                    killed,
+                });
+            }
+            if ret < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+
+            if limits.wall_clock().is_some_and(|wall| start.elapsed() > wall) {
+                killed = Some(Exceeded::Time);
+            } else if limits.memory.is_some_and(|mb| rss(pid).is_some_and(|kb| kb > mb * 1024)) {
+                killed = Some(Exceeded::Memory);
+            }
+            if killed.is_some() {
+                child.kill()?;
+            } else {
+                thread::sleep(POLL);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::{io, process::{Child, Command}, thread, time::Instant};
+
+    use super::{Exceeded, Limits, Usage, POLL};
+
+    pub(super) fn apply(_cmd: &mut Command, _limits: &Limits) {}
+
+    /// 只能限制墙钟时间，无法测量CPU时间与内存
+    pub(super) fn wait(child: &mut Child, start: Instant, limits: &Limits) -> io::Result<Usage> {
+        let mut killed = None;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Usage { status, wall: start.elapsed(), cpu: None, memory: None, killed });
+            }
+            if killed.is_none() && limits.wall_clock().is_some_and(|wall| start.elapsed() > wall) {
+                killed = Some(Exceeded::Time);
+                child.kill()?;
+            }
+            thread::sleep(POLL);
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_clock_limit_test() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("10");
+        let limits = Limits { time: Some(Duration::from_millis(100)), memory: None };
+        let usage = spawn(&mut cmd, limits).unwrap().wait().unwrap();
+        assert_eq!(usage.killed, Some(Exceeded::Time));
+        assert!(!usage.status.success());
+        assert!(usage.wall < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::HashMap, io::{BufRead, BufReader, Read, Write}, net::{TcpListener, TcpStream}, path::{Path, PathBuf}};
+
+use anyhow::{bail, Context};
+use log::{error, info, warn};
+use serde_derive::Deserialize;
+
+use crate::{build, cfg::ProjectTemplate, create_file_with_content, flags::Flags, plan::Output, project::{self, Manifest, Problem}};
+
+/// 请求体大小上限，避免异常请求占满内存
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// Competitive Companion 发送的题目，只保留用得到的字段
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Task {
+    name: String,
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    interactive: bool,
+    /// 单位为MB
+    memory_limit: u64,
+    /// 单位为ms
+    time_limit: u64,
+    tests: Vec<Sample>,
+    batch: Option<Batch>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Sample {
+    input: String,
+    output: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Batch {
+    id: String,
+    size: usize,
+}
+
+/// 监听`127.0.0.1:<port>`，为收到的每道题目生成一个项目
+pub(crate) fn serve(port: u16, std: &str, toolchain: &Path, template: &ProjectTemplate, flags: &Flags, once: bool, keep_on_error: bool) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .with_context(|| format!("Failed to listen on port {port}"))?;
+    info!("Waiting for Competitive Companion on 127.0.0.1:{port}");
+
+    // batch id -> 已收到的题目数
+    let mut batches: HashMap<String, usize> = HashMap::new();
+    let mut failures = Vec::new();
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept connection: {e}");
+                continue;
+            }
+        };
+        let task = match receive(&mut stream) {
+            Ok(task) => task,
+            Err(e) => {
+                warn!("Ignoring invalid request: {e:#}");
+                continue;
+            }
+        };
+        match import(&task, std, toolchain, template, flags, keep_on_error) {
+            Ok(dir) => info!("Created `{}` with {} sample(s)", dir.display(), task.tests.len()),
+            // `--once`时失败在退出时一并报告，避免重复
+            Err(e) if once => failures.push((task.name.clone(), e)),
+            Err(e) => error!("Failed to import `{}`: {e:#}", task.name),
+        }
+
+        // 没有`batch`的请求视为只有一道题目的批次
+        let (id, size) = task.batch.map_or((String::new(), 1), |batch| (batch.id, batch.size));
+        let received = batches.entry(id).or_default();
+        *received += 1;
+        if once && *received >= size {
+            break;
+        }
+    }
+    // 一直监听时失败只记录日志，`--once`时以失败退出
+    if failures.len() > 1 {
+        let details: Vec<_> = failures.iter().map(|(name, e)| format!("`{name}`: {e:#}")).collect();
+        bail!("Failed to import {} problems:\n{}", failures.len(), details.join("\n"));
+    }
+    match failures.pop() {
+        Some((name, e)) => Err(e.context(format!("Failed to import `{name}`"))),
+        None => Ok(()),
+    }
+}
+
+/// 读取一个HTTP请求并应答，返回解析出的题目
+fn receive(stream: &mut TcpStream) -> anyhow::Result<Task> {
+    let result = read_request(stream)
+        .and_then(|body| serde_json::from_slice::<Task>(&body).context("Invalid problem JSON"));
+    let status = match result {
+        Ok(_) => "200 OK",
+        Err(_) => "400 Bad Request",
+    };
+    // 应答失败不影响已经收到的题目
+    if let Err(e) = write!(stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n") {
+        warn!("Failed to respond: {e}");
+    }
+    result
+}
+
+/// 解析HTTP请求，只接受带`Content-Length`的POST请求，返回请求体
+fn read_request(stream: &mut impl Read) -> anyhow::Result<Vec<u8>> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("POST ") {
+        bail!("Unexpected request `{}`", line.trim_end());
+    }
+
+    let mut length = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("Unexpected end of request headers");
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            if key.trim().eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>().context("Invalid `Content-Length`")?);
+            }
+        }
+    }
+
+    let length = length.context("Missing `Content-Length`")?;
+    if length > MAX_BODY {
+        bail!("Request body is too large ({length} bytes)");
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+/// 把题目名转换为可用的目录名，只保留ASCII字母和数字
+fn sanitize(name: &str) -> String {
+    let mut result = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            result.push(c);
+        } else if !result.is_empty() && !result.ends_with('_') {
+            result.push('_');
+        }
+    }
+    result.trim_end_matches('_').to_owned()
+}
+
+/// 在当前目录下为题目生成项目，同一批次的多道题目放在以比赛命名的目录中
+fn import(task: &Task, std: &str, toolchain: &Path, template: &ProjectTemplate, flags: &Flags, keep_on_error: bool) -> anyhow::Result<PathBuf> {
+    let mut dir = PathBuf::new();
+    if task.batch.as_ref().is_some_and(|batch| batch.size > 1) {
+        let group = sanitize(&task.group);
+        if !group.is_empty() {
+            dir.push(group);
+        }
+    }
+    let name = sanitize(&task.name);
+    if name.is_empty() {
+        bail!("Cannot derive a directory name from `{}`", task.name);
+    }
+    dir.push(name);
+    // 不覆盖已有的项目
+    if dir.exists() {
+        bail!("Destination `{}` already exists", dir.display());
+    }
+
+    let mut manifest = Manifest {
+        problem: Some(Problem {
+            name: task.name.clone(),
+            group: task.group.clone(),
+            url: task.url.clone(),
+            time_limit: task.time_limit,
+            memory_limit: task.memory_limit,
+            interactive: task.interactive,
+        }),
+        std: std.to_owned(),
+        toolchain: toolchain.to_string_lossy().replace('\\', "/"),
+        flags: flags.clone(),
+        ..Default::default()
+    };
+    project::staged(&dir, keep_on_error, |staging| {
+        project::create(&mut Output::Disk, &dir, staging, template, &mut manifest, &build::supported_profiles(toolchain))?;
+        for (i, sample) in task.tests.iter().enumerate() {
+            create_file_with_content(&staging.join(format!("tests/{}.in", i + 1)), &sample.input)?;
+            create_file_with_content(&staging.join(format!("tests/{}.ans", i + 1)), &sample.output)?;
+        }
+        // 第一组样例同时作为调试输入
+        if let Some(sample) = task.tests.first() {
+            create_file_with_content(&staging.join("data.in"), &sample.input)?;
+        }
+        Ok(())
+    })?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_request_test() {
+        let body = r#"{"name":"A. Watermelon"}"#;
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: localhost:10043\r\ncontent-length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        assert_eq!(read_request(&mut request.as_bytes()).unwrap(), body.as_bytes());
+
+        let request = "GET / HTTP/1.1\r\n\r\n";
+        assert!(read_request(&mut request.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn sanitize_test() {
+        assert_eq!(sanitize("A. Watermelon"), "A_Watermelon");
+        assert_eq!(sanitize("Codeforces - Round #900 (Div. 3)"), "Codeforces_Round_900_Div_3");
+        assert_eq!(sanitize("西瓜"), "");
+    }
+}
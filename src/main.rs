@@ -1,26 +1,60 @@
-use std::{collections::HashMap, fs::File, io::Write, path::{Path, PathBuf}, process::{exit, Command}, str::FromStr};
+use std::{fs::File, io::Write, path::{Path, PathBuf}, process::exit};
 
 use anyhow::Context;
-use structopt::StructOpt;
-use log::{error, info, warn};
-use text_placeholder::Template;
-use serde_derive::{Serialize, Deserialize};
+use structopt::{clap, StructOpt};
+use log::info;
 
+use error::XcppError;
+
+mod add;
+mod build;
+mod bundle;
 mod cfg;
+mod config;
+mod coverage;
+mod doctor;
+mod error;
+mod escape;
+mod flags;
+mod framework;
+mod judge;
+mod limit;
+mod listen;
+mod memcheck;
+mod message;
+mod paths;
+mod pch;
+mod plan;
+mod project;
+
+const fn cpp_standards() -> [&'static str; 7] {
+    ["c++98", "c++03", "c++11", "c++14", "c++17", "c++20", "c++23"]
+}
 
-#[derive(Default, Debug, Serialize, Deserialize)]
-struct MyConfig {
-    std: String,
-    mingw64_path: String,
+fn template_names() -> Vec<&'static str> {
+    cfg::TEMPLATES.iter().map(|template| template.name).collect()
 }
 
-const fn cpp_standards() -> [&'static str; 8] {
-    ["c++98", "c++03", "c++11", "c++14", "c++17", "c++20", "c++23", "cfg"]
+fn find_template(name: &str) -> &'static cfg::ProjectTemplate {
+    // 只用于内置的模板名，一定能找到
+    cfg::TEMPLATES.iter().find(|template| template.name == name).unwrap()
+}
+
+/// `cp`模板的`debug.h`用到了`auto`、可变参数模板等C++11特性
+fn check_template(template: &cfg::ProjectTemplate, std: &config::Value<String>) -> Result<(), XcppError> {
+    if template.name == "cp" && matches!(std.value.as_str(), "c++98" | "c++03") {
+        return Err(XcppError::Config(format!("`--template cp` requires C++11 or later, but the std is `{}` ({})", std.value, std.origin)));
+    }
+    Ok(())
 }
 
 #[derive(StructOpt)]
 #[structopt(author = "Xanadu13")]
 struct Cli {
+    /// The format of messages, `json` prints one event per line to stdout for editors and scripts,
+    /// ending with a summary, and moves the human-readable output to stderr.
+    #[structopt(long, global = true, default_value = "human", possible_values = &message::MessageFormat::NAMES)]
+    message_format: message::MessageFormat,
     #[structopt(subcommand)]
     cmd: Cmd,
 }
@@ -31,12 +65,42 @@ enum Cmd {
     New {
         /// The name of your cpp project.
         name: String,
-        /// Cpp standard, will be passed as `--std=<std>` when compile .cpp files.
-        #[structopt(long, default_value = "cfg", possible_values = &cpp_standards())]
-        std: String,
-        /// The path of mingw64, e.g. `E:/Environment/mingw64_14_2_0/bin` in my Windows OS.
-        #[structopt(long, default_value = "")]
-        path: String,
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        /// The project template, `cp` adds fast IO, `<bits/stdc++.h>` and a `dbg(...)` macro for competitive programming.
+        /// Defaults to `XCPP_TEMPLATE`, then `template` in `config.toml`, then `default`.
+        #[structopt(long, possible_values = &template_names())]
+        template: Option<String>,
+        /// Precompile `bits/stdc++.h` for every build task, or the headers given as `--pch=<header>,...`, see `xcpp pch`.
+        #[structopt(long, min_values = 0, require_equals = true, use_delimiter = true)]
+        pch: Option<Vec<String>>,
+        /// The project layout, `src` puts sources in `src/`, public headers in `include/<name>/` and tests in `tests/`.
+        /// Defaults to `flat` for `bin` and `src` for libraries.
+        #[structopt(long, possible_values = &project::Layout::NAMES)]
+        layout: Option<project::Layout>,
+        /// What the project builds, libraries come with an `example/` program that links them.
+        #[structopt(long, default_value = "bin", possible_values = &project::Kind::NAMES)]
+        kind: project::Kind,
+        /// Set up unit tests in `tests/`, single-header frameworks are copied from the local cache.
+        #[structopt(long, possible_values = &framework::TestFramework::NAMES)]
+        test_framework: Option<framework::TestFramework>,
+        /// Create the project even if its name contains non-ASCII characters, or its directory or the mingw64 path contains
+        /// spaces or non-ASCII characters, quoting them in the generated files. Names with spaces are always refused.
+        /// Debugging in VSCode may still fail on such paths.
+        #[structopt(long)]
+        allow_unsafe_paths: bool,
+        /// Keep the partially generated project in `.<name>.xcpp-staging` when generation fails, for debugging.
+        #[structopt(long)]
+        keep_on_error: bool,
+        /// Print the files that would be created or overwritten, the template variables and the commands that would run,
+        /// without touching the disk.
+        #[structopt(long)]
+        dry_run: bool,
+        /// With `--dry-run`, also print the content of every generated file.
+        #[structopt(long, requires = "dry-run")]
+        show_content: bool,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
     },
     /// Store the config to `%appdata%\xcpp\config\config.toml`.
     Store {
@@ -46,236 +110,392 @@ enum Cmd {
         /// The path of mingw64, e.g. `E:/Environment/mingw64_14_2_0/bin` in my Windows OS.
         #[structopt(long, parse(from_os_str))]
         path: std::path::PathBuf,
+        /// The template used by `new` and `listen` when `--template` is not given.
+        #[structopt(long, possible_values = &template_names())]
+        template: Option<String>,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
     },
     /// Delete the config file at `%appdata%\xcpp\config\config.toml`.
     Clear {
 
-    }
+    },
+    /// Receive problems from the Competitive Companion browser extension.
+    Listen {
+        /// The port that Competitive Companion sends problems to.
+        #[structopt(long, default_value = "10043")]
+        port: u16,
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        /// The project template, `cp` adds fast IO, `<bits/stdc++.h>` and a `dbg(...)` macro for competitive programming.
+        /// Defaults to `XCPP_TEMPLATE`, then `template` in `config.toml`, then `default`.
+        #[structopt(long, possible_values = &template_names())]
+        template: Option<String>,
+        /// Exit after the first batch of problems has been received.
+        #[structopt(long)]
+        once: bool,
+        /// Create the projects even if the current directory or the mingw64 path contains spaces or non-ASCII characters,
+        /// quoting them in the generated files. Debugging in VSCode may still fail on such paths.
+        #[structopt(long)]
+        allow_unsafe_paths: bool,
+        /// Keep the partially generated project in `.<name>.xcpp-staging` when generation fails, for debugging.
+        #[structopt(long)]
+        keep_on_error: bool,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+    },
+    /// Build the project in the current directory.
+    Build {
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+        /// The flags to build with, the same as the build task of that name. Sanitizers need support from the toolchain.
+        #[structopt(long, default_value = "O2", possible_values = &cfg::profile_names())]
+        profile: String,
+        /// Build the unit tests in `tests/` instead and run them.
+        #[structopt(long)]
+        tests: bool,
+    },
+    /// Measure line coverage of the unit tests, or of a run with `data.in`, and write reports to `target/coverage/`.
+    Coverage {
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+    },
+    /// Build the project in the current directory and run it with `data.in` as input.
+    Run {
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+        /// Run under valgrind and report memory errors and leaks, fails if any are found.
+        #[structopt(long)]
+        memcheck: bool,
+        #[structopt(flatten)]
+        options: judge::Options,
+    },
+    /// Build the project in the current directory and check it against `tests/*.in` and `tests/*.ans`.
+    Test {
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+        #[structopt(flatten)]
+        options: judge::Options,
+    },
+    /// Precompile headers into `target/pch` for the project in the current directory.
+    Pch {
+        /// The headers to precompile, defaults to those in `xcpp.toml` or `bits/stdc++.h`.
+        headers: Vec<String>,
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+    },
+    /// Add a class, header or test to the project in the current directory.
+    Add(add::Item),
+    /// Show the settings in effect and where they come from.
+    Config(config::ConfigCmd),
+    /// Check the toolchain, make, git and `config.toml` for common problems and suggest fixes.
+    Doctor {
+
+    },
+    /// Regenerate `.vscode/` and the makefile of the project in the current directory from `xcpp.toml`.
+    Upgrade {
+        #[structopt(flatten)]
+        toolchain: config::ToolchainArgs,
+        #[structopt(flatten)]
+        flags: flags::FlagArgs,
+        /// Print the files that would be created or overwritten, the template variables and the commands that would run,
+        /// without touching the disk.
+        #[structopt(long)]
+        dry_run: bool,
+        /// With `--dry-run`, also print the content of every generated file.
+        #[structopt(long, requires = "dry-run")]
+        show_content: bool,
+    },
+    /// Inline the project headers into a single file for online judge submission.
+    Bundle {
+        /// The source file to start from, `main.cpp` in the source directory by default.
+        #[structopt(long, parse(from_os_str))]
+        entry: Option<PathBuf>,
+        /// The bundled file, kept in `target/` by default so that it won't be compiled with the project.
+        #[structopt(short, long, default_value = "target/submit.cpp", parse(from_os_str))]
+        output: PathBuf,
+        /// Remove `#ifdef LOCAL` blocks, keeping their `#else` branches.
+        #[structopt(long)]
+        strip_local: bool,
+    },
 }
 
-fn mkdir(path: &Path) {
+fn mkdir(path: &Path) -> Result<(), XcppError> {
     // 创建所有必要的父目录
-    if let Err(e) = std::fs::create_dir_all(path) {
-        panic!("Failed to create directories for {}: {:?}", path.display(), e);
-    }
+    std::fs::create_dir_all(path).map_err(XcppError::filesystem("create directory", path))
 }
 
-fn create_file(path: &Path) -> File {
+fn create_file(path: &Path) -> Result<File, XcppError> {
     if let Some(parent) = path.parent() {
-        mkdir(parent);
-    }
-    match File::create(&path) {
-        Ok(file) => file,
-        Err(e) => panic!("Failed to create {}: {:?}", path.display(), e),
+        mkdir(parent)?;
     }
+    let file = File::create(path).map_err(XcppError::filesystem("create", path))?;
+    message::file_created(path);
+    Ok(file)
 }
 
-fn create_file_with_content(path: &Path, content: &String) -> File {
-    let display = path.display();
-    let mut f = create_file(&path);
-    match f.write_all(content.as_bytes()) {
-        Ok(_) => info!("Successfully wrote to {}", display),
-        Err(e) => panic!("Failed to write to {}: {:?}", display, e),
-    }
-    f
+fn create_file_with_content(path: &Path, content: &str) -> Result<File, XcppError> {
+    let mut f = create_file(path)?;
+    f.write_all(content.as_bytes()).map_err(XcppError::filesystem("write to", path))?;
+    info!("Successfully wrote to {}", path.display());
+    Ok(f)
 }
 
-fn join_path<'a>(path: &'a PathBuf, sub: &'a str) -> Result<String, Box<dyn std::error::Error>> {
+fn join_path(path: &Path, sub: &str) -> anyhow::Result<String> {
     path.join(sub).as_path().to_str()
         .map(|str| str.to_owned())
-        .ok_or(format!("Invalid file path `{}`", path.display()).into())
+        .with_context(|| format!("Invalid file path `{}`", path.display()))
+}
+
+/// 全局配置文件`config.toml`所在的目录
+fn config_dir() -> anyhow::Result<PathBuf> {
+    let path = confy::get_configuration_file_path("xcpp", "config")?;
+    path.parent()
+        .map(Path::to_path_buf)
+        .with_context(|| format!("Invalid file path `{}`", path.display()))
 }
 
-/// 根据传入的config、std、path参数值，解析并返回(std, path)
-fn validate_args(config: MyConfig, std: String, path: String) -> (String, String) {
-    let std = match (std, config.std) {
-        (s1, _) if !matches!(s1.as_str(), "cfg") => s1, // 如果没有指定用配置文件内容，说明是c++__
-        (_, s2) => {
-            warn!("Missing argumet: `--std`, using `--std={s2}` in `config.toml`");
-            if cpp_standards().contains(&s2.as_str()) {
-                if matches!(s2.as_str(), "cfg") {
-                    panic!("`std=cfg` cannot be used in `config.toml`");
+/// 合并各层配置，`project`为真时读取当前目录下的`xcpp.toml`，mingw64路径必须存在
+fn resolve(cli: config::Layer, project: bool) -> anyhow::Result<config::Settings> {
+    let dir = std::env::current_dir().context("Failed to get current directory")?;
+    let settings = config::resolve(cli, &dir, project)?;
+    if settings.path.value.is_empty() {
+        return Err(XcppError::Config("Missing argument: `--path`, try to pass it, set `XCPP_PATH` or specify it in `config.toml`".to_owned()).into());
+    }
+    // 用户指定的mingw64路径不存在
+    let path = settings.toolchain();
+    if !path.exists() {
+        return Err(XcppError::Toolchain(format!(
+            "{} ({}) doesn't exist, setup of mingw64 is required first.\nYou can download it at https://github.com/niXman/mingw-builds-binaries/releases",
+            path.display(), settings.path.origin
+        )).into());
+    }
+    info!("Using `--std={}` from {} and `--path={}` from {}", settings.std.value, settings.std.origin, settings.path.value, settings.path.origin);
+    Ok(settings)
+}
+
+/// 参数解析失败时拿不到`Cli`，从原始参数中找出`--message-format`与子命令名
+fn scan_args(args: &[String]) -> (message::MessageFormat, &str) {
+    let (mut format, mut command) = (message::MessageFormat::Human, "");
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let value = match arg.strip_prefix("--message-format") {
+            Some("") => iter.next().map(String::as_str),
+            Some(value) => value.strip_prefix('='),
+            None => {
+                if command.is_empty() && !arg.starts_with('-') {
+                    command = arg;
                 }
-                s2
-            } else {
-                Cli::clap().print_help().unwrap();
-                panic!("Invalid argument in config.toml: `std={s2}`");
+                continue;
             }
+        };
+        format = value.and_then(|value| value.parse().ok()).unwrap_or(format);
+    }
+    (format, command)
+}
+
+// RUST_LOG=info ./xcpp.exe new hello_cpp --path E:/Environment/mingw64_14_2_0/bin --std=c++17
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let (format, command) = scan_args(&args);
+    let mut app = Cli::clap();
+    if format == message::MessageFormat::Json {
+        app = app.setting(clap::AppSettings::ColorNever);
+    }
+    let matches = match app.get_matches_from_safe(&args) {
+        Ok(matches) => matches,
+        // 帮助与版本信息照常打印，`json`时参数错误也以事件报告
+        Err(e) if format == message::MessageFormat::Json && e.use_stderr() => {
+            message::init(format);
+            let message = e.message.lines().next().unwrap_or_default().trim_start_matches("error: ").to_owned();
+            exit(message::finish(command, &Err(XcppError::Config(message).into())));
         }
-    };
-    let path = match (path, config.mingw64_path) {
-        (s1, _) if !s1.is_empty() => s1, // 如果s1非空，说明是经过验证的命令参数
-        (_, s2) => {
-            if s2.is_empty() {
-                Cli::clap().print_help().unwrap();
-                panic!("Missing argumet: `--path`, try to pass it or specify it in `config.toml`");
-            }
-            warn!("Missing argumet: `--path`, using `--path={s2}` in `config.toml`");
-            s2
+        // 参数错误与xcpp自己检查出的参数错误一样以2退出
+        Err(e) if e.use_stderr() => {
+            eprintln!("{}", e.message);
+            exit(XcppError::Config(String::new()).exit_code());
         }
+        Err(e) => e.exit(),
     };
-    (std, path)
-}
+    let args = Cli::from_clap(&matches);
+    message::init(args.message_format);
 
-// RUST_LOG=info ./xcpp.exe new hello_cpp --path E:/Environment/mingw64_14_2_0/bin --std=c++17
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
+    // 不打印调用栈，退出码见`XcppError::exit_code`
+    let code = message::finish(matches.subcommand_name().unwrap_or_default(), &run(args));
+    if code != 0 {
+        exit(code);
+    }
+}
 
-    let args = Cli::from_args();
+fn run(args: Cli) -> anyhow::Result<()> {
     match args.cmd {
         Cmd::Clear {  } => {
-            let appdata = std::env::var("APPDATA")
-                .expect("APPDATA environment variable not found")
-                + "\\xcpp\\config\\config.toml";
-            info!("Looking for `{appdata}`");
-            std::fs::remove_file(Path::new(&appdata))
-                .with_context(|| format!("Failed to delete file `config.toml`"))?;
+            let path = confy::get_configuration_file_path("xcpp", "config")?;
+            info!("Looking for `{}`", path.display());
+            std::fs::remove_file(&path).map_err(XcppError::filesystem("delete", &path))?;
         },
-        Cmd::Store { std, path } => {
+        Cmd::Store { std, path, template, flags } => {
             let path = path.to_str()
                 .with_context(|| format!("Invalid file path `{}`", path.display()))?
                 .to_owned()
                 .replace("\\", "/");
 
-            let cfg = MyConfig { std, mingw64_path: path };
+            let cfg = config::MyConfig {
+                version: config::CONFIG_VERSION,
+                std,
+                mingw64_path: path,
+                template: template.unwrap_or_default(),
+                flags: flags.layer().into_flags(),
+            };
             info!("Saving {:?} to `%appdata%\\xcpp\\config\\config.toml`", cfg);
             confy::store("xcpp", "config", cfg)?;
+            message::file_created(&confy::get_configuration_file_path("xcpp", "config")?);
         }
-        Cmd::New { name, std, path } => {
-            let config: MyConfig = confy::load("xcpp", "config")?;
-            let (std, path) = validate_args(config, std, path);
-
-            let path = PathBuf::from_str(path.as_str())
-                .with_context(|| format!("Invalid file path `{}`", path))?;
+        Cmd::New { name, toolchain, template, pch, layout, kind, test_framework, allow_unsafe_paths, keep_on_error, dry_run, show_content, flags } => {
+            let settings = resolve(toolchain.layer(config::Layer { template, ..flags.layer() }), false)?;
+            let path = settings.toolchain();
+            paths::check_name(&name, allow_unsafe_paths)?;
+            let dir = std::env::current_dir().context("Failed to get current directory")?;
+            paths::check_paths(&dir.join(&name), &path, allow_unsafe_paths)?;
 
-            // 用户指定的mingw64路径不存在
-            if !path.exists() {
-                error!("{} doesn't exist, setup of mingw64 is required first.\nYou can download it at https://github.com/niXman/mingw-builds-binaries/releases",
-                    path.display()
-                );
-                exit(-1);
-            }
-            
-            let name: &str = name.clone().leak();
-            let work_path = Path::new(name);
+            let work_path = Path::new(&name);
             // 要创建的工作路径已经存在，则终止
             if work_path.exists() {
-                error!("Destination `{}` already exists", work_path.display());
-                exit(-1);
-            }
-            // 创建项目工作目录
-            mkdir(work_path);
-            std::env::set_current_dir(work_path)
-                .with_context(|| format!("Failed to switch directory"))?;
-
-            let current_dir = std::env::current_dir()
-                .with_context(|| format!("Failed to get current directory"))?;
-            let current_dir = current_dir
-                .to_str()
-                .with_context(|| format!("Invalid file path `{}`", current_dir.display()))?
-                .to_owned()
-                .replace("\\", "/");
-
-            info!("current directory: {current_dir}");
-            
-            let gpp_path = join_path(&path, "g++.exe")?.replace("\\", "/");
-            let gdb_path = join_path(&path, "gdb.exe")?.replace("\\", "/");
-            let make_path = join_path(&path, "mingw32-make.exe")?.replace("\\", "/");
-            
-            let mut table = HashMap::new();
-            
-            table.insert("project", name);
-            table.insert("current_dir", current_dir.as_str());
-            table.insert("stdc++", std.as_str());
-            info!("using std={std}");
-            table.insert("g++", gpp_path.as_str());
-            info!("g++ path: `{gpp_path}`");
-            table.insert("gdb", gdb_path.as_str());
-            info!("gdb path: `{gdb_path}`");
-            table.insert("make", make_path.as_str());
-            info!("make path: `{make_path}`");
-
-            //exit(-1);
-            mkdir(Path::new("target"));
-
-            for file in cfg::FILES {
-                let template = Template::new(&file.content);
-                let content = template.fill_with_hashmap(&table);
-                let path = Path::new(file.path);
-                create_file_with_content(path, &content);
+                return Err(XcppError::Config(format!("Destination `{}` already exists", work_path.display())).into());
             }
-            create_file(Path::new("data.in"));
-            create_file(Path::new("data.out"));
-            create_file_with_content(
-                Path::new(".gitignore"), 
-                &".vscode/\ntarget/\n".to_owned()
-            );
-            create_file_with_content(
-                Path::new("main.cpp"), 
-        &
-r#"#include <iostream>
-using namespace std;
-
-int main(){
-    cout << "Hello, World" << '\n';
-    return 0;
-}
-"#.to_owned());
-
-            let output = Command::new("git")
-                .arg("init")
-                .output();
-
-            match output {
-                Ok(output) => {
-                    if output.status.success() {
-                        info!("{}", String::from_utf8_lossy(&output.stdout));
-                    } else {
-                        error!(
-                            "Failed to evaluate: `git init`\nstderr: {}", 
-                            String::from_utf8_lossy(&output.stderr)
-                        );
+            // 库项目需要区分公开头文件与源文件，只支持`src`布局
+            let layout = match (layout, kind.is_library()) {
+                (Some(project::Layout::Flat), true) => {
+                    return Err(XcppError::Config(format!("`--kind {}` requires `--layout src`", kind.name())).into());
+                }
+                (Some(layout), _) => layout,
+                (None, true) => project::Layout::Src,
+                (None, false) => project::Layout::Flat,
+            };
+            // 库项目不使用模板，只有命令行显式指定时才报错
+            let template = match (kind.is_library(), settings.template.origin) {
+                (false, _) => settings.template(),
+                (true, config::Origin::Cli) if settings.template.value != "default" => {
+                    return Err(XcppError::Config(format!("`--template {}` only applies to `--kind bin`", settings.template.value)).into());
+                }
+                (true, _) => find_template("default"),
+            };
+            check_template(template, &settings.std)?;
+            let mut manifest = project::Manifest {
+                pch: pch.map(|headers| pch::Pch { headers: pch::headers_or_default(headers) }),
+                layout,
+                kind,
+                test_framework,
+                std: settings.std.value.clone(),
+                toolchain: settings.path.value.replace('\\', "/"),
+                flags: settings.flags(),
+                ..Default::default()
+            };
+            let mut output = plan::Output::new(dry_run);
+            let profiles = build::planned_profiles(&mut output, &dir, &path);
+            let mut generate = |staging: &Path| {
+                project::create(&mut output, work_path, staging, template, &mut manifest, &profiles)?;
+                if let Some(pch) = &manifest.pch {
+                    if output.run(staging, pch::commands(staging, &path, &settings.std.value, &pch.headers, &manifest.flags, &profiles)) {
+                        pch::precompile(staging, &path, &settings.std.value, &pch.headers, &manifest.flags, &profiles)?;
                     }
                 }
-                Err(e) => {
-                    error!("Failed to evaluate: `git init`: {e}");
+                Ok(())
+            };
+            // 只打印计划时不需要临时目录
+            if dry_run {
+                generate(work_path)?;
+            } else {
+                project::staged(work_path, keep_on_error, generate)?;
+            }
+            if let plan::Output::DryRun(plan) = output {
+                plan.print(show_content);
+            }
+        },
+        Cmd::Listen { port, toolchain, template, once, allow_unsafe_paths, keep_on_error, flags } => {
+            let settings = resolve(toolchain.layer(config::Layer { template, ..flags.layer() }), false)?;
+            // 题目名会转换为ASCII的目录名，只需检查当前目录与工具链路径
+            let dir = std::env::current_dir().context("Failed to get current directory")?;
+            paths::check_paths(&dir, &settings.toolchain(), allow_unsafe_paths)?;
+            check_template(settings.template(), &settings.std)?;
+            listen::serve(port, &settings.std.value, &settings.toolchain(), settings.template(), &settings.flags(), once, keep_on_error)?;
+        },
+        Cmd::Build { toolchain, flags, profile, tests } => {
+            let settings = resolve(toolchain.layer(flags.layer()), true)?;
+            build::build(&settings.std.value, &settings.toolchain(), &settings.flags(), build::find_profile(&profile), tests)?;
+        },
+        Cmd::Coverage { toolchain, flags } => {
+            let settings = resolve(toolchain.layer(flags.layer()), true)?;
+            coverage::coverage(&settings.std.value, &settings.toolchain(), &settings.flags())?;
+        },
+        Cmd::Run { toolchain, flags, memcheck, options } => {
+            let settings = resolve(toolchain.layer(flags.layer()), true)?;
+            judge::run(&settings.std.value, &settings.toolchain(), &settings.flags(), options, memcheck)?;
+        },
+        Cmd::Test { toolchain, flags, options } => {
+            let settings = resolve(toolchain.layer(flags.layer()), true)?;
+            judge::test(&settings.std.value, &settings.toolchain(), &settings.flags(), options)?;
+        },
+        Cmd::Pch { headers, toolchain, flags } => {
+            let settings = resolve(toolchain.layer(flags.layer()), true)?;
+            let path = settings.toolchain();
+            let dir = std::env::current_dir().context("Failed to get current directory")?;
+            let mut manifest = project::Manifest::load(&dir)?.unwrap_or_default();
+            let headers = match (headers.is_empty(), &manifest.pch) {
+                (true, Some(pch)) => pch.headers.clone(),
+                _ => pch::headers_or_default(headers),
+            };
+            pch::precompile(&dir, &path, &settings.std.value, &headers, &settings.flags(), &build::supported_profiles(&path))?;
+            // 记录下来，之后`run`、`test`会自动更新
+            manifest.pch = Some(pch::Pch { headers });
+            manifest.store(&dir)?;
+        },
+        Cmd::Add(item) => {
+            add::add(item)?;
+        },
+        Cmd::Config(config::ConfigCmd::Show { origin }) => {
+            let dir = std::env::current_dir().context("Failed to get current directory")?;
+            config::show(&config::resolve(config::Layer::default(), &dir, true)?, origin);
+        },
+        Cmd::Doctor {  } => {
+            let dir = std::env::current_dir().context("Failed to get current directory")?;
+            doctor::doctor(&dir)?;
+        },
+        Cmd::Upgrade { toolchain, flags, dry_run, show_content } => {
+            let dir = std::env::current_dir().context("Failed to get current directory")?;
+            let Some(mut manifest) = project::Manifest::load(&dir)? else {
+                return Err(XcppError::Config(format!("No `{}` in `{}`, run `xcpp upgrade` in a project", project::MANIFEST, dir.display())).into());
+            };
+            let settings = resolve(toolchain.layer(flags.layer()), true)?;
+            let path = settings.toolchain();
+            let (std, flags) = (settings.std.value.as_str(), settings.flags());
+            let mut output = plan::Output::new(dry_run);
+            let profiles = build::planned_profiles(&mut output, &dir, &path);
+            project::upgrade(&mut output, &dir, &mut manifest, &settings, &profiles)?;
+            if let Some(pch) = &manifest.pch {
+                if output.run(&dir, pch::commands(&dir, &path, std, &pch.headers, &flags, &profiles)) {
+                    pch::precompile(&dir, &path, std, &pch.headers, &flags, &profiles)?;
                 }
             }
+            match output {
+                plan::Output::DryRun(plan) => plan.print(show_content),
+                plan::Output::Disk => info!("Upgraded `{}` to xcpp {}", dir.display(), project::VERSION),
+            }
+        },
+        Cmd::Bundle { entry, output, strip_local } => {
+            bundle::bundle(entry.as_deref(), &output, strip_local)?;
         },
     }
     
     Ok(())
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn validate_args_test1() {
-        let config = MyConfig {
-            std: "c++17".to_owned(),
-            mingw64_path: String::new(),
-        };
-        let std = "c++20".to_owned();
-        let path = "D:/wow/mingw64/bin".to_owned();
-
-        let (std, path) = validate_args(config, std, path);
-        // cli input overwrites config
-        assert_eq!(std, "c++20".to_owned()); 
-        assert_eq!(path, "D:/wow/mingw64/bin".to_owned());
-    }
-
-    #[test]
-    fn validate_args_test2() {
-        let config = MyConfig {
-            std: "c++14".to_owned(),
-            mingw64_path: "D:/wow/mingw64/bin".to_owned(),
-        };
-
-        let (std, path) = validate_args(config, String::from("cfg"), String::new());
-        // uses config
-        assert_eq!(std, "c++14".to_owned());
-        assert_eq!(path, "D:/wow/mingw64/bin".to_owned());
-    }
-}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use std::{fs::File, path::Path, process::{Command, Stdio}};
+
+use anyhow::{bail, Context};
+use log::info;
+
+use crate::{create_file, message::{self, report}};
+
+/// valgrind的完整报告
+const LOG: &str = "target/memcheck.log";
+
+/// 一处内存错误或泄漏，`location`为调用栈中第一个位于项目内的源文件位置
+#[derive(Debug, PartialEq, Eq)]
+struct Issue {
+    kind: String,
+    location: Option<String>,
+}
+
+/// valgrind报告中的内存错误与`LEAK SUMMARY`，泄漏的单位为字节
+#[derive(Debug, Default)]
+struct Report {
+    issues: Vec<Issue>,
+    definitely_lost: u64,
+    indirectly_lost: u64,
+    possibly_lost: u64,
+}
+
+impl Report {
+    fn leaked(&self) -> u64 {
+        self.definitely_lost + self.indirectly_lost + self.possibly_lost
+    }
+}
+
+/// valgrind是否可用，MinGW等环境下没有valgrind
+pub(crate) fn available() -> bool {
+    Command::new("valgrind")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// 在valgrind下以`input`为输入、`output`为输出运行`binary`，打印内存错误与泄漏的汇总
+pub(crate) fn run(binary: &Path, input: &Path, output: &Path) -> anyhow::Result<()> {
+    let dir = std::env::current_dir().context("Failed to get current directory")?;
+    let log = dir.join(LOG);
+    info!("Running `{}` under valgrind", binary.display());
+    let mut cmd = Command::new("valgrind");
+    cmd.arg("--leak-check=full")
+        // 显示完整路径，以区分项目源文件与系统库
+        .arg("--fullpath-after=")
+        .arg(format!("--log-file={}", log.display()))
+        .arg(binary)
+        .stdin(File::open(input).with_context(|| format!("Failed to open `{}`", input.display()))?)
+        .stdout(create_file(output)?);
+    message::command_run(&cmd);
+    let status = cmd.status()
+        .context("Failed to run `valgrind`")?;
+    let content = std::fs::read_to_string(&log)
+        .with_context(|| format!("Failed to read `{}`", log.display()))?;
+
+    let report = parse(&content, &dir);
+    for issue in &report.issues {
+        report!("{:<60} {}", issue.kind, issue.location.as_deref().unwrap_or("?"));
+    }
+    report!(
+        "Leaked: {} bytes definitely, {} bytes indirectly, {} bytes possibly",
+        report.definitely_lost, report.indirectly_lost, report.possibly_lost
+    );
+    report!("Full report written to `{}`", log.display());
+    if !report.issues.is_empty() || report.leaked() > 0 {
+        bail!("Memcheck found {} problems, {} bytes leaked", report.issues.len(), report.leaked());
+    }
+    if !status.success() {
+        bail!("Program exited with {status}");
+    }
+    Ok(())
+}
+
+/// 解析valgrind的文本报告，报告按空行分块，带调用栈的块为一处错误或泄漏
+fn parse(content: &str, dir: &Path) -> Report {
+    let mut report = Report::default();
+    // 每行以`==pid== `开头
+    let lines: Vec<&str> = content.lines()
+        .filter_map(|line| line.strip_prefix("=="))
+        .map(|line| line.split_once("== ").map_or("", |(_, rest)| rest))
+        .collect();
+    for block in lines.split(|line| line.trim().is_empty()) {
+        let Some(first) = block.first() else {
+            continue;
+        };
+        let frames: Vec<&str> = block.iter()
+            .map(|line| line.trim())
+            .filter(|line| line.starts_with("at 0x") || line.starts_with("by 0x"))
+            .collect();
+        if !frames.is_empty() {
+            // 泄漏记录形如`40 bytes in 1 blocks are definitely lost in loss record 1 of 1`
+            let kind = first.split(" in loss record").next().unwrap_or_default().trim();
+            let location = frames.iter().find_map(|frame| location(frame, dir));
+            report.issues.push(Issue { kind: kind.to_owned(), location });
+            continue;
+        }
+        for line in block {
+            let line = line.trim();
+            let (field, bytes) = match line.split_once(": ") {
+                Some((field, rest)) => (field, rest.split(" bytes").next().unwrap_or_default().replace(',', "")),
+                None => continue,
+            };
+            let Ok(bytes) = bytes.parse() else {
+                continue;
+            };
+            match field {
+                "definitely lost" => report.definitely_lost = bytes,
+                "indirectly lost" => report.indirectly_lost = bytes,
+                "possibly lost" => report.possibly_lost = bytes,
+                _ => {}
+            }
+        }
+    }
+    report
+}
+
+/// 从`at 0x109156: main (/tmp/p/main.cpp:5)`中取出项目内的源文件位置
+fn location(frame: &str, dir: &Path) -> Option<String> {
+    let (_, source) = frame.rsplit_once(" (")?;
+    let (file, line) = source.strip_suffix(')')?.rsplit_once(':')?;
+    let line: u32 = line.parse().ok()?;
+    // `--fullpath-after=`下有调试信息的文件均为绝对路径，相对路径来自valgrind自身
+    let path = Path::new(file);
+    if !path.is_absolute() || !path.starts_with(dir) || path.starts_with(dir.join("target")) {
+        return None;
+    }
+    let file = path.strip_prefix(dir).ok()?.display().to_string().replace('\\', "/");
+    Some(format!("{file}:{line}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let content = "\
+==42== Memcheck, a memory error detector
+==42== Command: /tmp/p/target/p
+==42==
+==42== Invalid read of size 4
+==42==    at 0x109176: main (/tmp/p/src/main.cpp:6)
+==42==  Address 0x4a8e068 is 0 bytes after a block of size 40 alloc'd
+==42==    at 0x4846828: operator new[](unsigned long) (vg_replace_malloc.c:640)
+==42==    by 0x109163: main (/tmp/p/src/main.cpp:5)
+==42==
+==42== HEAP SUMMARY:
+==42==     in use at exit: 1,064 bytes in 2 blocks
+==42==
+==42== 1,064 (40 direct, 1,024 indirect) bytes in 1 blocks are definitely lost in loss record 2 of 2
+==42==    at 0x4846828: operator new[](unsigned long) (in /usr/libexec/valgrind/vgpreload_memcheck-amd64-linux.so)
+==42==    by 0x109163: main (/tmp/p/src/main.cpp:5)
+==42==
+==42== LEAK SUMMARY:
+==42==    definitely lost: 40 bytes in 1 blocks
+==42==    indirectly lost: 1,024 bytes in 1 blocks
+==42==      possibly lost: 0 bytes in 0 blocks
+==42==    still reachable: 0 bytes in 0 blocks
+==42==
+==42== ERROR SUMMARY: 2 errors from 2 contexts (suppressed: 0 from 0)
+";
+        let report = parse(content, Path::new("/tmp/p"));
+        assert_eq!(report.issues, [
+            Issue { kind: "Invalid read of size 4".to_owned(), location: Some("src/main.cpp:6".to_owned()) },
+            Issue {
+                kind: "1,064 (40 direct, 1,024 indirect) bytes in 1 blocks are definitely lost".to_owned(),
+                location: Some("src/main.cpp:5".to_owned()),
+            },
+        ]);
+        assert_eq!((report.definitely_lost, report.indirectly_lost, report.possibly_lost), (40, 1024, 0));
+    }
+}
@@ -0,0 +1,213 @@
+use std::{path::{Path, PathBuf}, process::{Command, Stdio}, str::FromStr, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Mutex}};
+
+use anyhow::bail;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_derive::Serialize;
+
+use crate::error;
+
+/// `--message-format`，`json`时stdout上每行是一个事件，供编辑器插件与脚本解析
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MessageFormat {
+    Human,
+    Json,
+}
+
+impl MessageFormat {
+    pub(crate) const NAMES: [&'static str; 2] = ["human", "json"];
+}
+
+impl FromStr for MessageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => bail!("Invalid message format `{s}`"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    FileCreated { path: String },
+    CommandRun { program: String, args: Vec<String>, cwd: Option<String> },
+    Warning { message: String },
+    Error { message: String, code: i32 },
+    /// 每次执行的最后一个事件
+    Summary { command: &'a str, success: bool, code: i32, files_created: usize, commands_run: usize, warnings: usize },
+}
+
+impl Event<'_> {
+    fn command_run(cmd: &Command) -> Event<'static> {
+        Event::CommandRun {
+            program: cmd.get_program().to_string_lossy().into_owned(),
+            args: cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+            cwd: cmd.get_current_dir().map(|dir| dir.display().to_string()),
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static FILES_CREATED: AtomicUsize = AtomicUsize::new(0);
+static COMMANDS_RUN: AtomicUsize = AtomicUsize::new(0);
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+/// 生成项目期间写入临时目录的文件，成功重命名后才按最终路径报告，见`project::staged`
+static STAGED: Mutex<Option<Vec<PathBuf>>> = Mutex::new(None);
+
+fn emit(event: &Event) {
+    if JSON.load(Ordering::Relaxed) {
+        // 事件中只有字符串与数字，一定能序列化
+        println!("{}", serde_json::to_string(event).unwrap());
+    }
+}
+
+/// 在`env_logger`之外把警告与错误转为事件，不受`RUST_LOG`影响
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        match record.level() {
+            Level::Warn => {
+                WARNINGS.fetch_add(1, Ordering::Relaxed);
+                emit(&Event::Warning { message: record.args().to_string() });
+            }
+            // 如`xcpp listen`中导入失败的题目，命令本身仍继续执行
+            Level::Error => emit(&Event::Error { message: record.args().to_string(), code: 1 }),
+            _ => {}
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+pub(crate) fn init(format: MessageFormat) {
+    if format == MessageFormat::Human {
+        env_logger::init();
+        return;
+    }
+    JSON.store(true, Ordering::Relaxed);
+    let inner = env_logger::Builder::from_default_env().build();
+    let level = inner.filter().max(LevelFilter::Warn);
+    if log::set_boxed_logger(Box::new(Logger { inner })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+pub(crate) fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// 给用户看的结果，`json`时改为输出到stderr，stdout只留给事件
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if $crate::message::is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use report;
+
+/// 子进程的stdout，`json`时同样改为输出到stderr
+pub(crate) fn child_stdout() -> Stdio {
+    if is_json() {
+        std::io::stderr().into()
+    } else {
+        Stdio::inherit()
+    }
+}
+
+/// 不受`RUST_LOG`影响、总会显示的警告，`json`时为警告事件
+pub(crate) fn warning(message: &str) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    if is_json() {
+        emit(&Event::Warning { message: message.to_owned() });
+    } else {
+        eprintln!("Warning: {message}");
+    }
+}
+
+pub(crate) fn file_created(path: &Path) {
+    FILES_CREATED.fetch_add(1, Ordering::Relaxed);
+    if let Some(staged) = STAGED.lock().unwrap().as_mut() {
+        staged.push(path.to_owned());
+        return;
+    }
+    emit(&Event::FileCreated { path: path.display().to_string() });
+}
+
+/// 之后创建的文件暂不报告，直到`end_staging`
+pub(crate) fn begin_staging() {
+    *STAGED.lock().unwrap() = Some(Vec::new());
+}
+
+/// 结束暂存，`kept`为`Some`时把`staging`中的文件按其中的路径报告，为`None`时文件已被删除，不再报告
+pub(crate) fn end_staging(staging: &Path, kept: Option<&Path>) {
+    let staged = STAGED.lock().unwrap().take().unwrap_or_default();
+    match kept {
+        Some(dir) => {
+            for path in staged {
+                let path = path.strip_prefix(staging).map_or(path.clone(), |relative| dir.join(relative));
+                emit(&Event::FileCreated { path: path.display().to_string() });
+            }
+        }
+        None => {
+            FILES_CREATED.fetch_sub(staged.len(), Ordering::Relaxed);
+        }
+    }
+}
+
+pub(crate) fn command_run(cmd: &Command) {
+    COMMANDS_RUN.fetch_add(1, Ordering::Relaxed);
+    emit(&Event::command_run(cmd));
+}
+
+/// 报告`command`的结果，返回退出码
+pub(crate) fn finish(command: &str, result: &anyhow::Result<()>) -> i32 {
+    let code = result.as_ref().err().map_or(0, error::exit_code);
+    if let Err(e) = result {
+        if is_json() {
+            emit(&Event::Error { message: format!("{e:#}"), code });
+        } else {
+            eprintln!("Error: {e:#}");
+        }
+    }
+    emit(&Event::Summary {
+        command,
+        success: result.is_ok(),
+        code,
+        files_created: FILES_CREATED.load(Ordering::Relaxed),
+        commands_run: COMMANDS_RUN.load(Ordering::Relaxed),
+        warnings: WARNINGS.load(Ordering::Relaxed),
+    });
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_test() {
+        let event = Event::Error { message: "`/mingw64/bin` doesn't exist".to_owned(), code: 3 };
+        assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"event":"error","message":"`/mingw64/bin` doesn't exist","code":3}"#);
+        let mut cmd = Command::new("git");
+        cmd.arg("init").current_dir("hello_cpp");
+        assert_eq!(serde_json::to_string(&Event::command_run(&cmd)).unwrap(), r#"{"event":"command_run","program":"git","args":["init"],"cwd":"hello_cpp"}"#);
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use log::warn;
+
+use crate::error::XcppError;
+
+/// 路径中会导致VSCode无法调试的字符
+pub(crate) fn problem(path: &str) -> Option<&'static str> {
+    if path.chars().any(char::is_whitespace) {
+        Some("contains spaces")
+    } else if !path.is_ascii() {
+        Some("contains non-ASCII characters")
+    } else {
+        None
+    }
+}
+
+/// 项目名同时是目录名与makefile中的目标名，不能含有路径分隔符、Windows文件名中的非法字符与空格
+pub(crate) fn check_name(name: &str, allow_unsafe: bool) -> anyhow::Result<()> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(XcppError::Config(format!("Invalid project name `{name}`")).into());
+    }
+    if let Some(c) = name.chars().find(|&c| "/\\<>:\"|?*".contains(c) || c.is_control()) {
+        return Err(XcppError::Config(format!("Invalid project name `{name}`: `{}` can't be used in a directory name", c.escape_default())).into());
+    }
+    if name.chars().any(char::is_whitespace) {
+        return Err(XcppError::Config(format!("Invalid project name `{name}`: make can't build targets with spaces, use `_` or `-` instead")).into());
+    }
+    if !allow_unsafe && !name.is_ascii() {
+        return Err(XcppError::Config(format!("Project name `{name}` contains non-ASCII characters, which break debugging in VSCode. \
+            Use an ASCII name, or pass `--allow-unsafe-paths` to create it anyway")).into());
+    }
+    Ok(())
+}
+
+/// 检查新项目所在的目录与工具链路径，`allow_unsafe`为真时只给出提示，生成的文件会为这些路径加上引号
+pub(crate) fn check_paths(dest: &Path, toolchain: &Path, allow_unsafe: bool) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+    for (what, path) in [("Destination", dest), ("Toolchain path", toolchain)] {
+        let path = path.to_string_lossy().replace('\\', "/");
+        if let Some(problem) = problem(&path) {
+            problems.push(format!("{what} `{path}` {problem}"));
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    if allow_unsafe {
+        for problem in &problems {
+            warn!("{problem}, quoting it in the generated files");
+        }
+        return Ok(());
+    }
+    Err(XcppError::Config(format!("{}.\nVSCode and gdb often fail to debug projects on such paths, move them to a path with only ASCII characters and no spaces, \
+        or pass `--allow-unsafe-paths` to quote them in the generated files", problems.join(".\n"))).into())
+}
+
+/// makefile中作为命令使用的路径，转义后含空格时加上双引号
+pub(crate) fn make_command(path: &str) -> String {
+    let path = crate::escape::makefile(path);
+    if path.chars().any(char::is_whitespace) {
+        format!("\"{path}\"")
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problem_test() {
+        assert_eq!(problem("E:/Environment/mingw64_14_2_0/bin"), None);
+        assert_eq!(problem("C:/Program Files/mingw64/bin"), Some("contains spaces"));
+        assert_eq!(problem("D:/工具/mingw64/bin"), Some("contains non-ASCII characters"));
+    }
+
+    #[test]
+    fn check_name_test() {
+        assert!(check_name("hello_cpp", false).is_ok());
+        assert!(check_name("你好", false).is_err());
+        assert!(check_name("你好", true).is_ok());
+        assert!(check_name("hello cpp", true).is_err());
+        assert!(check_name("a/b", true).is_err());
+        assert!(check_name("..", true).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{path::Path, process::Command, thread};
+
+use anyhow::{bail, Context};
+use log::info;
+use serde_derive::{Serialize, Deserialize};
+
+use crate::{build, cfg::Profile, create_file_with_content, error::XcppError, flags::Flags, message};
+
+/// 预编译头文件的输出目录，生成的任务与makefile都以`-I`引用它
+pub(crate) const DIR: &str = "target/pch";
+
+/// 未指定时预编译的头文件
+pub(crate) const DEFAULT_HEADER: &str = "bits/stdc++.h";
+
+/// `xcpp.toml`中的`[pch]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Pch {
+    pub(crate) headers: Vec<String>,
+}
+
+/// 未指定头文件时使用`DEFAULT_HEADER`
+pub(crate) fn headers_or_default(headers: Vec<String>) -> Vec<String> {
+    if headers.is_empty() {
+        vec![DEFAULT_HEADER.to_owned()]
+    } else {
+        headers
+    }
+}
+
+/// 为每个头文件、每组编译参数生成`target/pch/<header>.gch/<profile>.gch`。
+/// g++会在`<header>.gch`目录中挑选与当前编译参数匹配的一个使用，都不匹配时照常编译原头文件。
+/// `profile`的参数按`flags`调整，已有的`.gch`在std与编译参数都未改变时跳过
+pub(crate) fn precompile(dir: &Path, toolchain: &Path, std: &str, headers: &[String], flags: &Flags, profiles: &[&Profile]) -> anyhow::Result<()> {
+    let gpp = build::tool(toolchain, "g++");
+    let root = dir.join(DIR);
+    for header in headers {
+        // 以一个只包含该头文件的文件作为预编译的输入，它不在`-I`的搜索路径中
+        let stub = root.join(".stub").join(header);
+        create_file_with_content(&stub, &format!("#include <{header}>\n"))?;
+        let output_dir = root.join(format!("{header}.gch"));
+        crate::mkdir(&output_dir)?;
+
+        // 各组参数互不影响，并行编译
+        let results = thread::scope(|scope| {
+            let handles: Vec<_> = profiles.iter()
+                .map(|profile| {
+                    let (gpp, stub) = (&gpp, &stub);
+                    let gch = output_dir.join(format!("{}.gch", profile.name));
+                    let stamp = root.join(".stamp").join(header).join(profile.name);
+                    let args = flags.compile(profile);
+                    scope.spawn(move || precompile_one(gpp, std, &args, header, stub, &gch, &stamp))
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+        });
+        for result in results {
+            result.with_context(|| format!("Failed to precompile `{header}`"))?;
+        }
+    }
+    Ok(())
+}
+
+/// 生成`gch`，编译参数记录在`stamp`中用于判断是否需要重新编译。
+/// `stamp`不能放在`.gch`目录中，否则g++会逐个尝试并报告为无效的预编译头文件
+fn precompile_one(gpp: &Path, std: &str, args: &[String], header: &str, stub: &Path, gch: &Path, stamp: &Path) -> anyhow::Result<()> {
+    let mut cmd = command(gpp, std, args, stub, gch);
+    let fingerprint = fingerprint(gpp, std, args, header);
+    if is_fresh(gch, stamp, &fingerprint) {
+        info!("`{}` is up to date", gch.display());
+        return Ok(());
+    }
+
+    info!("Precompiling `{}`", gch.display());
+    message::command_run(&cmd);
+    let status = cmd.status()
+        .map_err(|e| XcppError::Toolchain(format!("Failed to evaluate `{}`: {e}", gpp.display())))?;
+    if !status.success() {
+        bail!("Failed to generate `{}`", gch.display());
+    }
+    create_file_with_content(stamp, &fingerprint)?;
+    Ok(())
+}
+
+/// 记录在`stamp`中的g++、std、编译参数与头文件，不含项目中的路径，项目移动或重命名后仍然有效
+fn fingerprint(gpp: &Path, std: &str, args: &[String], header: &str) -> String {
+    let args: Vec<_> = pch_args(args).collect();
+    format!("{} -std={std} {args:?} {header}", gpp.display())
+}
+
+/// `gch`存在，并且生成它时的`fingerprint`相同，std或编译参数改变后需要重新编译
+fn is_fresh(gch: &Path, stamp: &Path, fingerprint: &str) -> bool {
+    gch.exists() && std::fs::read_to_string(stamp).is_ok_and(|old| old == fingerprint)
+}
+
+/// `--coverage`会在`gch`旁生成`.gcno`，预编译时去掉它，生成的`gch`仍可用于覆盖率编译
+fn pch_args(args: &[String]) -> impl Iterator<Item = &String> {
+    args.iter().filter(|arg| *arg != "--coverage")
+}
+
+fn command(gpp: &Path, std: &str, args: &[String], stub: &Path, gch: &Path) -> Command {
+    let mut cmd = Command::new(gpp);
+    cmd.arg("-fdiagnostics-color=always")
+        .arg(format!("-std={std}"))
+        .args(pch_args(args))
+        .arg("-x")
+        .arg("c++-header")
+        .arg(stub)
+        .arg("-o")
+        .arg(gch);
+    cmd
+}
+
+/// `precompile`会执行的命令，用于`--dry-run`，不判断是否已是最新
+pub(crate) fn commands(dir: &Path, toolchain: &Path, std: &str, headers: &[String], flags: &Flags, profiles: &[&Profile]) -> Vec<String> {
+    let gpp = build::tool(toolchain, "g++");
+    let root = dir.join(DIR);
+    headers.iter()
+        .flat_map(|header| profiles.iter().map(move |profile| (header, profile)))
+        .map(|(header, profile)| {
+            let stub = root.join(".stub").join(header);
+            let gch = root.join(format!("{header}.gch")).join(format!("{}.gch", profile.name));
+            format!("{:?}", command(&gpp, std, &flags.compile(profile), &stub, &gch))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::PROFILES;
+
+    #[test]
+    fn commands_test() {
+        let dir = Path::new("hello_cpp");
+        let headers = ["bits/stdc++.h".to_owned()];
+        let coverage = build::find_profile("coverage");
+        let planned = commands(dir, Path::new("/mingw64/bin"), "c++17", &headers, &Flags::default(), &[build::profile(), coverage]);
+        assert_eq!(planned.len(), 2);
+        assert!(planned[0].contains("\"-std=c++17\"") && planned[0].contains("\"-O2\""));
+        assert!(planned[0].ends_with("O2.gch\""));
+        assert!(planned[1].ends_with("coverage.gch\"") && !planned[1].contains("--coverage"));
+        assert_eq!(commands(dir, Path::new("/mingw64/bin"), "c++17", &headers, &Flags::default(), &PROFILES.iter().collect::<Vec<_>>()).len(), PROFILES.len());
+    }
+
+    #[test]
+    fn stamp_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let gpp = build::tool(Path::new("/mingw64/bin"), "g++");
+        let args = Flags::default().compile(build::profile());
+        let old = fingerprint(&gpp, "c++17", &args, "vector");
+        let (gch, stamp) = (tmp.path().join("hello_cpp/target/pch/vector.gch/O2.gch"), tmp.path().join("hello_cpp/target/pch/.stamp/vector/O2"));
+        assert!(!is_fresh(&gch, &stamp, &old));
+
+        create_file_with_content(&gch, "").unwrap();
+        create_file_with_content(&stamp, &old).unwrap();
+        assert!(is_fresh(&gch, &stamp, &old));
+        assert!(!is_fresh(&gch, &stamp, &fingerprint(&gpp, "c++20", &args, "vector")));
+        assert!(!is_fresh(&gch, &stamp, &fingerprint(&gpp, "c++17", &["-DN=100".to_owned()], "vector")));
+
+        // `xcpp new`在临时目录中预编译后重命名，之后不需要重新编译
+        std::fs::rename(tmp.path().join("hello_cpp"), tmp.path().join("moved")).unwrap();
+        let (gch, stamp) = (tmp.path().join("moved/target/pch/vector.gch/O2.gch"), tmp.path().join("moved/target/pch/.stamp/vector/O2"));
+        assert!(is_fresh(&gch, &stamp, &fingerprint(&gpp, "c++17", &args, "vector")));
+    }
+}
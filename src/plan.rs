@@ -0,0 +1,167 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use anyhow::Context;
+use log::info;
+
+use crate::{create_file_with_content, error::XcppError, message::{self, report}, mkdir};
+
+/// 计划中的一个文件，`exists`为真时会覆盖已有的文件
+struct Entry {
+    path: PathBuf,
+    source: Source,
+    exists: bool,
+}
+
+enum Source {
+    Text(String),
+    /// 从本地缓存复制，只记录大小
+    Copy(PathBuf, u64),
+}
+
+impl Entry {
+    fn size(&self) -> u64 {
+        match &self.source {
+            Source::Text(content) => content.len() as u64,
+            Source::Copy(_, size) => *size,
+        }
+    }
+}
+
+/// `--dry-run`时记录的生成计划：模板变量、要写入的文件与要执行的命令
+#[derive(Default)]
+pub(crate) struct Plan {
+    variables: Vec<(&'static str, String)>,
+    files: Vec<Entry>,
+    /// 命令与执行它的目录
+    commands: Vec<(String, PathBuf)>,
+}
+
+impl Plan {
+    /// 打印计划，`show_content`为真时再打印每个文件生成的内容
+    pub(crate) fn print(&self, show_content: bool) {
+        if !self.variables.is_empty() {
+            report!("Variables:");
+            let width = self.variables.iter().map(|(key, _)| key.len()).max().unwrap_or_default();
+            for (key, value) in &self.variables {
+                report!("  {key:<width$} = {value:?}");
+            }
+        }
+        report!("Files:");
+        let width = self.files.iter().map(|file| file.path.to_string_lossy().len()).max().unwrap_or_default();
+        for file in &self.files {
+            let action = if file.exists { "overwrite" } else { "create" };
+            report!("  {action:<9} {:<width$}  {} bytes", file.path.display().to_string(), file.size());
+        }
+        if !self.commands.is_empty() {
+            report!("Commands:");
+            for (command, dir) in &self.commands {
+                report!("  {command}  (in `{}`)", dir.display());
+            }
+        }
+        if show_content {
+            for file in &self.files {
+                report!("\n==> {} <==", file.path.display());
+                match &file.source {
+                    Source::Text(content) => report!("{}", content.strip_suffix('\n').unwrap_or(content)),
+                    Source::Copy(from, _) => report!("(copied from `{}`)", from.display()),
+                }
+            }
+        }
+    }
+}
+
+/// 生成的文件写到哪里，`DryRun`时只记录到`Plan`中，不改动磁盘
+pub(crate) enum Output {
+    Disk,
+    DryRun(Plan),
+}
+
+impl Output {
+    pub(crate) fn new(dry_run: bool) -> Output {
+        if dry_run {
+            Output::DryRun(Plan::default())
+        } else {
+            Output::Disk
+        }
+    }
+
+    /// 记录填充模板所用的变量，按名称排序
+    pub(crate) fn variables(&mut self, table: &HashMap<&'static str, String>) {
+        if let Output::DryRun(plan) = self {
+            plan.variables = table.iter().map(|(&key, value)| (key, value.clone())).collect();
+            plan.variables.sort();
+        }
+    }
+
+    pub(crate) fn mkdir(&mut self, path: &Path) -> Result<(), XcppError> {
+        match self {
+            Output::Disk => mkdir(path),
+            Output::DryRun(_) => Ok(()),
+        }
+    }
+
+    pub(crate) fn write(&mut self, path: &Path, content: &str) -> Result<(), XcppError> {
+        match self {
+            Output::Disk => create_file_with_content(path, content).map(drop),
+            Output::DryRun(plan) => {
+                plan.files.push(Entry { path: path.to_owned(), source: Source::Text(content.to_owned()), exists: path.exists() });
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn copy(&mut self, from: &Path, to: &Path) -> anyhow::Result<()> {
+        match self {
+            Output::Disk => {
+                if let Some(parent) = to.parent() {
+                    mkdir(parent)?;
+                }
+                std::fs::copy(from, to).with_context(|| format!("Failed to copy `{}`", from.display()))?;
+                message::file_created(to);
+                info!("Copied `{}` to `{}`", from.display(), to.display());
+            }
+            Output::DryRun(plan) => {
+                let size = std::fs::metadata(from).map_err(XcppError::filesystem("read", from))?.len();
+                plan.files.push(Entry { path: to.to_owned(), source: Source::Copy(from.to_owned(), size), exists: to.exists() });
+            }
+        }
+        Ok(())
+    }
+
+    /// 记录将在`dir`中执行的命令，返回是否应当真正执行
+    pub(crate) fn run(&mut self, dir: &Path, commands: impl IntoIterator<Item = String>) -> bool {
+        match self {
+            Output::Disk => true,
+            Output::DryRun(plan) => {
+                plan.commands.extend(commands.into_iter().map(|command| (command, dir.to_owned())));
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let existing = tmp.path().join("makefile");
+        std::fs::write(&existing, "all:\n").unwrap();
+
+        let mut output = Output::new(true);
+        output.mkdir(&tmp.path().join("target")).unwrap();
+        output.write(&existing, "all: main\n").unwrap();
+        output.write(&tmp.path().join("data.in"), "").unwrap();
+        assert!(!output.run(tmp.path(), ["git init".to_owned()]));
+
+        let Output::DryRun(plan) = output else { unreachable!() };
+        assert!(!tmp.path().join("target").exists());
+        assert!(!tmp.path().join("data.in").exists());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "all:\n");
+        let files: Vec<_> = plan.files.iter().map(|file| (file.exists, file.size())).collect();
+        assert_eq!(files, [(true, 10), (false, 0)]);
+        assert_eq!(plan.commands.len(), 1);
+    }
+}
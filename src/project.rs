@@ -0,0 +1,520 @@
+use std::{collections::HashMap, path::Path, process::Command, str::FromStr};
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+use serde_derive::{Serialize, Deserialize};
+use text_placeholder::Template;
+
+use crate::{add, build, cfg::{self, ConfigFile, Profile, ProjectTemplate}, config::Settings, flags::Flags, framework::{self, TestFramework}, error::XcppError, escape::{self, Format}, memcheck, message, paths, pch::Pch, plan::Output, create_file_with_content, join_path};
+
+/// 项目元数据文件名，位于项目根目录
+pub(crate) const MANIFEST: &str = "xcpp.toml";
+
+/// 生成的文件对应的xcpp版本，记录在`xcpp.toml`中
+pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 项目元数据，对应项目根目录下的 `xcpp.toml`，项目的配置以它为准
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) name: String,
+    /// 最近一次生成配置文件的xcpp版本，由 `xcpp new`、`xcpp upgrade` 写入
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) version: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) std: String,
+    /// 工具链所在目录，即mingw64的`bin`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) toolchain: String,
+    /// 生成项目时使用的模板，见`cfg::TEMPLATES`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) template: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) problem: Option<Problem>,
+    /// 需要预编译的头文件，由 `xcpp new --pch` 写入
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) pch: Option<Pch>,
+    #[serde(default)]
+    pub(crate) layout: Layout,
+    #[serde(default)]
+    pub(crate) kind: Kind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) test_framework: Option<TestFramework>,
+    /// 追加的警告、宏定义、头文件目录与链接库，由 `xcpp new --warnings`等写入
+    #[serde(default, skip_serializing_if = "Flags::is_default")]
+    pub(crate) flags: Flags,
+}
+
+/// 项目的目录结构
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Layout {
+    /// 源文件都位于项目根目录
+    #[default]
+    Flat,
+    /// 源文件位于`src/`，公开的头文件位于`include/<project>/`，测试位于`tests/`
+    Src,
+}
+
+impl Layout {
+    pub(crate) const NAMES: [&'static str; 2] = ["flat", "src"];
+
+    /// 源文件所在目录，相对于项目根目录
+    pub(crate) fn source_dir(self) -> &'static str {
+        match self {
+            Layout::Flat => "",
+            Layout::Src => "src",
+        }
+    }
+
+    /// 编译时额外的`-I`目录，相对于项目根目录
+    pub(crate) fn include_dirs(self) -> &'static [&'static str] {
+        match self {
+            Layout::Flat => &[],
+            Layout::Src => &["include"],
+        }
+    }
+}
+
+/// 项目生成的目标
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Kind {
+    /// 可执行文件
+    #[default]
+    Bin,
+    /// 静态库`lib<project>.a`
+    Staticlib,
+    /// 动态库`lib<project>.so`，Windows下为`lib<project>.dll`
+    Sharedlib,
+    /// 只有头文件的库
+    HeaderOnly,
+}
+
+impl Kind {
+    pub(crate) const NAMES: [&'static str; 4] = ["bin", "staticlib", "sharedlib", "header-only"];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Kind::Bin => "bin",
+            Kind::Staticlib => "staticlib",
+            Kind::Sharedlib => "sharedlib",
+            Kind::HeaderOnly => "header-only",
+        }
+    }
+
+    pub(crate) fn is_library(self) -> bool {
+        self != Kind::Bin
+    }
+}
+
+impl FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bin" => Ok(Kind::Bin),
+            "staticlib" => Ok(Kind::Staticlib),
+            "sharedlib" => Ok(Kind::Sharedlib),
+            "header-only" => Ok(Kind::HeaderOnly),
+            _ => bail!("Invalid project kind `{s}`"),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Layout::Flat),
+            "src" => Ok(Layout::Src),
+            _ => bail!("Invalid layout `{s}`"),
+        }
+    }
+}
+
+/// 题目信息，由 `xcpp listen` 写入
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Problem {
+    #[serde(default)]
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) group: String,
+    #[serde(default)]
+    pub(crate) url: String,
+    /// 时间限制，单位为ms，0表示不限制
+    #[serde(default)]
+    pub(crate) time_limit: u64,
+    /// 内存限制，单位为MB，0表示不限制
+    #[serde(default)]
+    pub(crate) memory_limit: u64,
+    #[serde(default)]
+    pub(crate) interactive: bool,
+}
+
+impl Manifest {
+    /// 读取`dir`下的`xcpp.toml`，文件不存在时返回`None`
+    pub(crate) fn load(dir: &Path) -> anyhow::Result<Option<Manifest>> {
+        let path = dir.join(MANIFEST);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read `{}`", path.display()))?;
+        let manifest = toml::from_str(&content)
+            .with_context(|| format!("Invalid manifest `{}`", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    pub(crate) fn store(&self, dir: &Path) -> anyhow::Result<()> {
+        create_file_with_content(&dir.join(MANIFEST), &self.to_toml()?)?;
+        Ok(())
+    }
+
+    pub(crate) fn to_toml(&self) -> anyhow::Result<String> {
+        toml::to_string(self).context("Failed to serialize the project manifest")
+    }
+}
+
+/// 为`work_path`生成项目，文件写入`staging`，配置中的路径仍按`work_path`生成，见`staged`。
+/// std与工具链取自`manifest`，项目名与模板补充到其中写入`xcpp.toml`。`profiles`为工具链支持的编译参数，用于生成任务、makefile与调试配置。
+/// 文件与命令经过`output`，`--dry-run`时只记录下来
+pub(crate) fn create(output: &mut Output, work_path: &Path, staging: &Path, template: &ProjectTemplate, manifest: &mut Manifest, profiles: &[&Profile]) -> anyhow::Result<()> {
+    let name = work_path.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid project name `{}`", work_path.display()))?;
+
+    // 创建项目工作目录
+    output.mkdir(staging)?;
+
+    manifest.name = name.to_owned();
+    manifest.version = VERSION.to_owned();
+    manifest.template = template.name.to_owned();
+    let table = render_table(work_path, manifest, profiles)?;
+    output.variables(&table);
+    let table = borrow(&table);
+    let (layout, kind, test_framework) = (manifest.layout, manifest.kind, manifest.test_framework);
+
+    output.mkdir(&staging.join("target"))?;
+    if layout == Layout::Src {
+        output.mkdir(&staging.join("include").join(name))?;
+        output.mkdir(&staging.join("tests"))?;
+    }
+
+    write_config_files(output, staging, &table)?;
+    if kind.is_library() {
+        // 库项目生成公开头文件、源文件与链接它的示例程序，路径中同样有占位符
+        let files: &[ConfigFile] = match kind {
+            Kind::HeaderOnly => &cfg::HEADER_ONLY_FILES,
+            _ => &cfg::LIBRARY_FILES,
+        };
+        for file in files {
+            let path = Template::new(file.path).fill_with_hashmap(&table);
+            let content = Template::new(file.content).fill_with_hashmap(&table);
+            output.write(&staging.join(path), &content)?;
+        }
+    } else {
+        info!("using template `{}`", template.name);
+        for file in template.files {
+            let template = Template::new(file.content);
+            let content = template.fill_with_hashmap(&table);
+            output.write(&staging.join(layout.source_dir()).join(file.path), &content)?;
+        }
+    }
+    if let Some(test_framework) = test_framework {
+        info!("using test framework `{}`", test_framework.name());
+        framework::vendor(output, staging, test_framework)?;
+        if let Some(file) = test_framework.main() {
+            output.write(&staging.join(file.path), file.content)?;
+        }
+        let mut table = table.clone();
+        table.insert("name", "example");
+        table.insert("suite", "Example");
+        let content = add::load_template(staging, test_framework.item())?;
+        output.write(
+            &staging.join("tests/example_test.cpp"),
+            &Template::new(&content).fill_with_hashmap(&table)
+        )?;
+    }
+    output.write(&staging.join("data.in"), "")?;
+    output.write(&staging.join("data.out"), "")?;
+    output.write(
+        &staging.join(".gitignore"),
+        ".vscode/\ntarget/\n"
+    )?;
+    output.write(&staging.join(MANIFEST), &manifest.to_toml()?)?;
+
+    if !output.run(staging, ["git init".to_owned()]) {
+        return Ok(());
+    }
+    let mut cmd = Command::new("git");
+    cmd.arg("init").current_dir(staging);
+    message::command_run(&cmd);
+    let output = cmd.output();
+
+    // 没有安装git时仍然可以使用项目，`xcpp doctor`会给出提示
+    match output {
+        Ok(output) if output.status.success() => info!("{}", String::from_utf8_lossy(&output.stdout)),
+        Ok(output) => {
+            return Err(XcppError::Vcs(format!(
+                "Failed to evaluate: `git init`\nstderr: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )).into());
+        }
+        Err(e) => warn!("Failed to evaluate: `git init`: {e}"),
+    }
+    Ok(())
+}
+
+/// 在`work_path`旁的临时目录中执行`generate`，成功后整体重命名为`work_path`，避免失败时留下不完整的项目。
+/// 失败时删除临时目录，`keep_on_error`为真时保留以便排查
+pub(crate) fn staged(work_path: &Path, keep_on_error: bool, generate: impl FnOnce(&Path) -> anyhow::Result<()>) -> anyhow::Result<()> {
+    let name = work_path.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid project name `{}`", work_path.display()))?;
+    // 与`work_path`位于同一目录，重命名不会跨文件系统
+    let staging = work_path.with_file_name(format!(".{name}.xcpp-staging"));
+    // 上次以`--keep-on-error`保留下来的
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging).map_err(XcppError::filesystem("remove", &staging))?;
+    }
+    message::begin_staging();
+    let result = generate(&staging).and_then(|()| {
+        std::fs::rename(&staging, work_path).map_err(XcppError::filesystem("rename", &staging))?;
+        Ok(())
+    });
+    if result.is_ok() {
+        message::end_staging(&staging, Some(work_path));
+    } else if keep_on_error && staging.exists() {
+        eprintln!("Kept the partial project at `{}`", staging.display());
+        message::end_staging(&staging, Some(&staging));
+    } else {
+        message::end_staging(&staging, None);
+        if staging.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&staging) {
+                warn!("Failed to clean up `{}`: {e}", staging.display());
+            }
+        }
+    }
+    result
+}
+
+/// 按`xcpp.toml`重新生成`.vscode/`中的配置与makefile，不改动源文件
+/// 按生效的`settings`重新生成配置文件，`xcpp.toml`中只写入来自命令行或它本身的配置
+pub(crate) fn upgrade(output: &mut Output, dir: &Path, manifest: &mut Manifest, settings: &Settings, profiles: &[&Profile]) -> anyhow::Result<()> {
+    if manifest.name.is_empty() {
+        manifest.name = dir.file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("Invalid project directory `{}`", dir.display()))?
+            .to_owned();
+    }
+    manifest.version = VERSION.to_owned();
+    let effective = Manifest {
+        std: settings.std.value.clone(),
+        toolchain: settings.path.value.replace('\\', "/"),
+        flags: settings.flags(),
+        ..manifest.clone()
+    };
+    let table = render_table(dir, &effective, profiles)?;
+    output.variables(&table);
+    write_config_files(output, dir, &borrow(&table))?;
+    settings.persist(manifest);
+    output.write(&dir.join(MANIFEST), &manifest.to_toml()?)?;
+    Ok(())
+}
+
+/// 已经按所在文件的格式生成的片段，填充时不再转义
+const FRAGMENTS: [&str; 13] = [
+    "args", "link_args", "tasks", "test_launch", "sanitizer_launch", "include_path", "defines",
+    "cxx", "ar", "links", "modes", "sources", "includes",
+];
+
+/// 生成配置文件所需的全部占位符
+fn render_table(work_path: &Path, manifest: &Manifest, profiles: &[&Profile]) -> anyhow::Result<HashMap<&'static str, String>> {
+    let (name, std) = (manifest.name.as_str(), manifest.std.as_str());
+    let toolchain = Path::new(&manifest.toolchain);
+    let current_dir = std::env::current_dir()
+        .context("Failed to get current directory")?
+        .join(work_path);
+    let current_dir = current_dir
+        .to_str()
+        .with_context(|| format!("Invalid file path `{}`", current_dir.display()))?
+        .replace("\\", "/");
+
+    info!("project directory: {current_dir}");
+
+    let mut table = HashMap::new();
+
+    table.insert("project", name.to_owned());
+    // 项目路径含空格时，make无法处理含空格的目标，改用相对路径，make总是在项目目录中运行；
+    // cppbuild任务会给含空格的参数加上引号，通配符不再展开，改为相对于工作目录`${fileDirname}`
+    let spaced = current_dir.chars().any(char::is_whitespace);
+    table.insert("tarpath", if spaced { "target/".to_owned() } else { format!("{current_dir}/target/") });
+    table.insert("task_sources", if spaced { "*.cpp" } else { "${fileDirname}/*.cpp" }.to_owned());
+    table.insert("stdc++", std.to_owned());
+    info!("using std={std}");
+    let gpp_path = join_path(toolchain, "g++.exe")?.replace("\\", "/");
+    info!("g++ path: `{gpp_path}`");
+    table.insert("cxx", paths::make_command(&gpp_path));
+    table.insert("g++", gpp_path);
+    let gdb_path = join_path(toolchain, "gdb.exe")?.replace("\\", "/");
+    info!("gdb path: `{gdb_path}`");
+    table.insert("gdb", gdb_path);
+    let make_path = join_path(toolchain, "mingw32-make.exe")?.replace("\\", "/");
+    info!("make path: `{make_path}`");
+    table.insert("make", make_path);
+    table.insert("ar", paths::make_command(&join_path(toolchain, "ar.exe")?.replace("\\", "/")));
+    table.insert("namespace", identifier(name));
+
+    let (layout, kind) = (manifest.layout, manifest.kind);
+    info!("using layout `{layout:?}`, kind `{}`", kind.name());
+    table.insert("kind", kind.name().to_owned());
+    let test_framework = manifest.test_framework;
+    table.insert("test_framework", test_framework.map_or("", TestFramework::name).to_owned());
+    // `src`布局下当前打开的文件不一定在项目根目录，调试时从项目根目录读取data.in
+    table.insert("launch_cwd", match layout {
+        Layout::Flat => "${fileDirname}",
+        Layout::Src => "${workspaceFolder}",
+    }.to_owned());
+    let flags = &manifest.flags;
+    let links = flags.links();
+    table.insert("link_args", links.iter()
+        .map(|flag| format!("\n                \"{}\",", escape::json(flag)))
+        .collect());
+    table.insert("links", links.iter().map(|flag| escape::makefile(flag)).collect::<Vec<_>>().join(" "));
+    let tasks = render_tasks(&borrow(&table), profiles, manifest, memcheck::available());
+    table.insert("tasks", tasks);
+    let test_launch = match test_framework {
+        Some(_) => escape::fill(cfg::TEST_LAUNCH, &borrow(&table), Format::Json, &FRAGMENTS),
+        None => String::new(),
+    };
+    table.insert("test_launch", test_launch);
+    table.insert("vendor_dir", framework::VENDOR_DIR.to_owned());
+    table.insert("test_libs", test_framework.map_or(String::new(), |framework| framework.libs().join(" ")));
+    let main = Path::new(layout.source_dir()).join("main.cpp");
+    table.insert("main", main.to_str().unwrap_or_default().replace('\\', "/"));
+    table.insert("modes", render_modes(std, profiles, flags));
+    table.insert("sources", match layout {
+        Layout::Flat => "$(filter-out target/% tests/% interactor.cpp,$(call rwildcard,,*.cpp))".to_owned(),
+        Layout::Src => format!("$(call rwildcard,{}/,*.cpp)", layout.source_dir()),
+    });
+    table.insert("includes", layout.include_dirs().iter()
+        .map(|dir| format!("-I{dir}"))
+        .chain(flags.includes(""))
+        .map(|flag| format!(" {}", escape::makefile(&flag)))
+        .collect());
+    table.insert("include_path", layout.include_dirs().iter()
+        .map(|dir| format!("${{workspaceFolder}}/{dir}"))
+        .chain(flags.include_paths("${workspaceFolder}"))
+        .map(|dir| format!("\n                \"{}\",", escape::json(&dir)))
+        .collect());
+    table.insert("defines", flags.defines.iter()
+        .map(|define| format!("\n                \"{}\",", escape::json(define)))
+        .collect());
+    let sanitizer_launch: String = profiles.iter()
+        .filter(|profile| profile.is_sanitizer())
+        .map(|profile| {
+            let mut table = borrow(&table);
+            table.insert("label", profile.label);
+            escape::fill(cfg::SANITIZER_LAUNCH, &table, Format::Json, &FRAGMENTS)
+        })
+        .collect();
+    table.insert("sanitizer_launch", sanitizer_launch);
+    Ok(table)
+}
+
+/// 借用`table`中的值，用于填充模板
+fn borrow<'a>(table: &'a HashMap<&'static str, String>) -> HashMap<&'static str, &'a str> {
+    table.iter().map(|(&key, value)| (key, value.as_str())).collect()
+}
+
+/// 按文件格式转义后写入`.vscode/`中的配置与makefile，JSON文件写入前先检查能否解析
+fn write_config_files(output: &mut Output, work_path: &Path, table: &HashMap<&str, &str>) -> anyhow::Result<()> {
+    for file in cfg::FILES.iter() {
+        let format = Format::of(file.path);
+        let content = escape::fill(file.content, table, format, &FRAGMENTS);
+        let path = work_path.join(file.path);
+        escape::validate(&path, &content, format)?;
+        output.write(&path, &content)?;
+    }
+    Ok(())
+}
+
+/// 按`profiles`生成tasks.json中的编译任务
+/// 库项目与`src`布局都需要makefile才能正确编译，此时任务改为调用make
+fn render_tasks(table: &HashMap<&str, &str>, profiles: &[&Profile], manifest: &Manifest, memcheck: bool) -> String {
+    // cppbuild任务总是生成`.exe`，make按平台决定后缀
+    let (task, exe) = match (manifest.layout, manifest.kind) {
+        (Layout::Flat, Kind::Bin) => (cfg::TASK, ".exe"),
+        _ => (cfg::MAKE_TASK, std::env::consts::EXE_SUFFIX),
+    };
+    profiles.iter()
+        .map(|profile| {
+            let flags: Vec<_> = manifest.flags.compile(profile).into_iter()
+                .chain(manifest.flags.includes("${workspaceFolder}"))
+                .collect();
+            let args = flags.iter()
+                .map(|flag| format!("                \"{}\",", escape::json(flag)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let detail = flags.iter()
+                .chain(&manifest.flags.links())
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut table = table.clone();
+            table.insert("label", profile.label);
+            table.insert("profile", profile.name);
+            table.insert("args", &args);
+            table.insert("detail", &detail);
+            escape::fill(task, &table, Format::Json, &FRAGMENTS)
+        })
+        .chain(manifest.test_framework.map(|_| escape::fill(cfg::TEST_TASKS, table, Format::Json, &FRAGMENTS)))
+        .chain(memcheck.then(|| {
+            let mut table = table.clone();
+            table.insert("label", build::find_profile("O1").label);
+            table.insert("exe", exe);
+            escape::fill(cfg::MEMCHECK_TASK, &table, Format::Json, &FRAGMENTS)
+        }))
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+/// 由项目名得到可用作C++命名空间的标识符
+pub(crate) fn identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// 按`profiles`生成makefile中各MODE的编译参数
+fn render_modes(std: &str, profiles: &[&Profile], flags: &Flags) -> String {
+    profiles.iter()
+        .map(|profile| format!(
+            "ifeq ($(MODE),{})\n    CXXFLAGS = -fdiagnostics-color=always -std={std} {}\nendif\n",
+            profile.name,
+            flags.compile(profile).iter().map(|flag| escape::makefile(flag)).collect::<Vec<_>>().join(" ")
+        ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memcheck_task_test() {
+        let table = HashMap::from([("launch_cwd", "${workspaceFolder}")]);
+        let manifest = Manifest { layout: Layout::Src, ..Default::default() };
+        let tasks = render_tasks(&table, &[], &manifest, true);
+        let program = format!("target/${{workspaceFolderBasename}}{}\\\" < data.in", std::env::consts::EXE_SUFFIX);
+        assert!(tasks.contains(&program), "{tasks}");
+        let manifest = Manifest { layout: Layout::Flat, ..Default::default() };
+        assert!(render_tasks(&table, &[], &manifest, true).contains("target/${workspaceFolderBasename}.exe\\\" < data.in"));
+    }
+}
@@ -1,12 +1,36 @@
 use assert_cmd::prelude::*;
 use log::info;
 use predicates::prelude::*;
-use std::process::Command;
+use std::{io::{Read, Write}, net::{TcpListener, TcpStream}, path::Path, process::{Command, Stdio}, thread, time::Duration};
+
+/// 在临时目录中运行xcpp，配置文件也写入该目录，避免影响本机配置
+fn xcpp(dir: &Path) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("xcpp")?;
+    cmd.current_dir(dir)
+        .env("HOME", dir)
+        .env("XDG_CONFIG_HOME", dir.join(".config"))
+        .env("APPDATA", dir.join("AppData"));
+    Ok(cmd)
+}
+
+/// 等待`xcpp listen`开始监听，发送一道题目，返回应答
+fn post(port: u16, body: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut stream = (0..50)
+        .find_map(|_| TcpStream::connect(("127.0.0.1", port)).ok().or_else(|| {
+            thread::sleep(Duration::from_millis(100));
+            None
+        }))
+        .expect("xcpp listen is not accepting connections");
+    write!(stream, "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}", body.len())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
 
 #[test]
 fn invalid_std_argument() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin("xcpp")?;
-    cmd.current_dir("tmp");
+    let tmp = tempfile::tempdir()?;
+    let mut cmd = xcpp(tmp.path())?;
     info!("{}", cmd.get_current_dir().unwrap().display());
     cmd.arg("new").arg("hello_cpp")
         .arg("--std=c++18"); // wrong argument
@@ -15,4 +39,363 @@ fn invalid_std_argument() -> Result<(), Box<dyn std::error::Error>> {
         .stderr(predicate::str::contains("'c++18' isn't a valid value for '--std <std>'"));
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn listen_imports_competitive_companion_problem() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let mut child = xcpp(tmp.path())?
+        .arg("listen")
+        .arg(format!("--port={port}"))
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--once")
+        .spawn()?;
+
+    let body = r#"{
+        "name": "A. Watermelon",
+        "group": "Codeforces - Beta Round 4",
+        "url": "https://codeforces.com/problemset/problem/4/A",
+        "interactive": false,
+        "memoryLimit": 64,
+        "timeLimit": 1000,
+        "tests": [{"input": "8\n", "output": "YES\n"}, {"input": "5\n", "output": "NO\n"}],
+        "batch": {"id": "123", "size": 1}
+    }"#;
+    assert!(post(port, body)?.starts_with("HTTP/1.1 200"));
+    assert!(child.wait()?.success());
+
+    let dir = tmp.path().join("A_Watermelon");
+    assert_eq!(std::fs::read_to_string(dir.join("tests/1.in"))?, "8\n");
+    assert_eq!(std::fs::read_to_string(dir.join("tests/2.ans"))?, "NO\n");
+    assert_eq!(std::fs::read_to_string(dir.join("data.in"))?, "8\n");
+    let manifest = std::fs::read_to_string(dir.join("xcpp.toml"))?;
+    assert!(manifest.contains("time_limit = 1000"));
+    assert!(manifest.contains("memory_limit = 64"));
+
+    Ok(())
+}
+
+#[test]
+fn listen_once_fails_without_batch() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    // 已有同名目录，导入失败
+    std::fs::create_dir(tmp.path().join("A_Watermelon"))?;
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let mut child = xcpp(tmp.path())?
+        .arg("listen")
+        .arg(format!("--port={port}"))
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--once")
+        .arg("--message-format=json")
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let body = r#"{"name": "A. Watermelon", "memoryLimit": 64, "timeLimit": 1000, "tests": []}"#;
+    assert!(post(port, body)?.starts_with("HTTP/1.1 200"));
+    let status = (0..50)
+        .find_map(|_| child.try_wait().ok().flatten().or_else(|| {
+            thread::sleep(Duration::from_millis(100));
+            None
+        }));
+    if status.is_none() {
+        child.kill()?;
+    }
+    assert!(status.is_some_and(|status| !status.success()), "`xcpp listen --once` should exit with an error");
+    // 失败只报告一次
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout)?;
+    assert_eq!(stdout.matches(r#""event":"error""#).count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn upgrade_regenerates_from_manifest() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .assert()
+        .success();
+
+    let dir = tmp.path().join("hello_cpp");
+    let manifest = std::fs::read_to_string(dir.join("xcpp.toml"))?;
+    assert!(manifest.contains("name = \"hello_cpp\""));
+    assert!(manifest.contains("std = \"c++17\""));
+    assert!(manifest.contains("template = \"default\""));
+    std::fs::write(dir.join("xcpp.toml"), manifest.replace("c++17", "c++20"))?;
+
+    xcpp(&dir)?.arg("upgrade").assert().success();
+    let makefile = std::fs::read_to_string(dir.join("makefile"))?;
+    assert!(makefile.contains("-std=c++20"));
+    assert!(!makefile.contains("-std=c++17"));
+
+    Ok(())
+}
+
+#[test]
+fn config_show_reports_origins() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++14")
+        .arg(format!("--path={}", tmp.path().display()))
+        .assert()
+        .success();
+
+    xcpp(&tmp.path().join("hello_cpp"))?
+        .arg("config").arg("show").arg("--origin")
+        .env("XCPP_LINKS", "m,pthread")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("std = \"c++14\""))
+        .stdout(predicate::str::contains("# xcpp.toml"))
+        .stdout(predicate::str::contains("links = [\"m\", \"pthread\"]  # environment `XCPP_LINKS`"));
+
+    Ok(())
+}
+
+#[test]
+fn missing_toolchain_exits_with_code() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().join("mingw64").display()))
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("doesn't exist"))
+        .stderr(predicate::str::contains("panicked").not());
+
+    Ok(())
+}
+
+#[test]
+fn failed_new_leaves_nothing_behind() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    // 临时目录中没有缓存doctest的头文件
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--test-framework=doctest")
+        .assert()
+        .code(5);
+    assert!(!tmp.path().join("hello_cpp").exists());
+    assert!(!tmp.path().join(".hello_cpp.xcpp-staging").exists());
+
+    Ok(())
+}
+
+#[test]
+fn dry_run_writes_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--dry-run").arg("--show-content")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stdc++           = \"c++17\""))
+        .stdout(predicate::str::is_match(r"create +hello_cpp/\.vscode/tasks\.json +\d+ bytes")?)
+        // sanitizer的检测只列出，不执行
+        .stdout(predicate::str::is_match(r"xcpp-probe-\d+-asan")?)
+        .stderr(predicate::str::contains("Skipping profile").not())
+        .stdout(predicate::str::contains("git init  (in `hello_cpp`)"))
+        .stdout(predicate::str::contains("==> hello_cpp/main.cpp <=="));
+    assert!(!tmp.path().join("hello_cpp").exists());
+
+    Ok(())
+}
+
+#[test]
+fn json_messages_end_with_summary() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let output = xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--message-format=json")
+        .output()?;
+    assert!(output.status.success());
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert!(events.iter().any(|event| event["event"] == "file_created" && event["path"] == "hello_cpp/xcpp.toml"));
+    let summary = events.last().unwrap();
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["command"], "new");
+    assert_eq!(summary["code"], 0);
+
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--message-format=json")
+        .assert()
+        .code(2)
+        .stdout(predicate::str::contains(r#"{"event":"error","message":"Destination `hello_cpp` already exists","code":2}"#));
+
+    Ok(())
+}
+
+#[test]
+fn json_reports_argument_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let output = xcpp(tmp.path())?
+        .arg("--message-format").arg("json")
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++99")
+        .output()?;
+    assert_eq!(output.status.code(), Some(2));
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "error");
+    assert_eq!(events[0]["message"], "'c++99' isn't a valid value for '--std <std>'");
+    assert_eq!((&events[1]["event"], &events[1]["command"], &events[1]["code"]), (&"summary".into(), &"new".into(), &2.into()));
+
+    // 帮助信息照常打印
+    xcpp(tmp.path())?
+        .arg("new").arg("--help")
+        .arg("--message-format=json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("USAGE:"));
+
+    Ok(())
+}
+
+#[test]
+fn exit_codes() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let new = |args: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = xcpp(tmp.path())?;
+        cmd.arg("new").args(args).arg("--std=c++17");
+        Ok(cmd)
+    };
+    let path = format!("--path={}", tmp.path().display());
+    std::fs::create_dir(tmp.path().join("taken"))?;
+    xcpp(tmp.path())?.arg("bundle").arg("--entry=missing.cpp").assert().code(1);
+    // 参数无效或冲突、项目名或目标目录不可用
+    for args in [&["hello_cpp", "--kind=dll"][..], &["hello cpp"], &["taken"], &["hello_cpp", "--kind=staticlib", "--layout=flat"]] {
+        new(args)?.arg(&path).assert().code(2);
+    }
+    xcpp(tmp.path())?.arg("add").arg("class").arg("1st").assert().code(2);
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--template=cp")
+        .arg("--std=c++98")
+        .arg(&path)
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("`--template cp` requires C++11 or later"));
+    new(&["hello_cpp", "--path=/does/not/exist"])?.assert().code(3);
+    xcpp(tmp.path())?.arg("clear").assert().code(4);
+    // 本地缓存中没有`doctest.h`
+    new(&["hello_cpp", "--test-framework=doctest"])?.arg(&path).assert().code(5);
+
+    Ok(())
+}
+
+#[test]
+fn pch_takes_headers_only_after_equals() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    xcpp(tmp.path())?
+        .arg("new").arg("--pch").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("hello_cpp/xcpp.toml"))
+        .stdout(predicate::str::contains("bits/stdc++.h"));
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp").arg("--pch=vector,map")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--dry-run").arg("--show-content")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#"headers = ["vector", "map"]"#));
+
+    Ok(())
+}
+
+#[test]
+fn add_class_in_both_layouts() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    for (name, layout, std) in [("flat_cpp", "flat", "c++11"), ("src_cpp", "src", "c++17")] {
+        xcpp(tmp.path())?
+            .arg("new").arg(name)
+            .arg(format!("--std={std}"))
+            .arg(format!("--layout={layout}"))
+            .arg(format!("--path={}", tmp.path().display()))
+            .assert()
+            .success();
+        xcpp(&tmp.path().join(name))?
+            .arg("add").arg("class").arg("Point")
+            .arg("--namespace=geo::detail")
+            .assert()
+            .success();
+    }
+
+    let flat = tmp.path().join("flat_cpp");
+    let header = std::fs::read_to_string(flat.join("Point.h"))?;
+    assert!(header.contains("namespace geo { namespace detail {\n\nclass Point {"));
+    assert!(header.contains("} // namespace detail\n} // namespace geo"));
+    let source = std::fs::read_to_string(flat.join("Point.cpp"))?;
+    assert!(source.starts_with("#include \"Point.h\"\n"));
+    assert!(source.contains("Point::Point() {"));
+
+    let src = tmp.path().join("src_cpp");
+    let header = std::fs::read_to_string(src.join("include/src_cpp/Point.h"))?;
+    assert!(header.contains("namespace geo::detail {\n\nclass Point {"));
+    let source = std::fs::read_to_string(src.join("src/Point.cpp"))?;
+    assert!(source.starts_with("#include <src_cpp/Point.h>\n"));
+    assert!(!src.join("Point.h").exists());
+
+    // 没有测试框架时`tests/`中的文件不会被编译
+    xcpp(&flat)?
+        .arg("add").arg("test").arg("point")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("The project has no test framework"));
+    assert!(!flat.join("tests/point_test.cpp").exists());
+
+    Ok(())
+}
+
+#[test]
+fn new_with_test_framework_vendors_header() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    // 与`xcpp`函数中的配置目录一致
+    let config = if cfg!(windows) { tmp.path().join("AppData/xcpp/config") } else { tmp.path().join(".config/xcpp") };
+    std::fs::create_dir_all(config.join("vendor"))?;
+    std::fs::write(config.join("vendor/doctest.h"), "// doctest stub\n")?;
+
+    xcpp(tmp.path())?
+        .arg("new").arg("hello_cpp")
+        .arg("--std=c++17")
+        .arg(format!("--path={}", tmp.path().display()))
+        .arg("--test-framework=doctest")
+        .assert()
+        .success();
+
+    let dir = tmp.path().join("hello_cpp");
+    assert_eq!(std::fs::read_to_string(dir.join("tests/vendor/doctest.h"))?, "// doctest stub\n");
+    assert!(std::fs::read_to_string(dir.join("tests/test_main.cpp"))?.contains("DOCTEST_CONFIG_IMPLEMENT_WITH_MAIN"));
+    assert!(std::fs::read_to_string(dir.join("tests/example_test.cpp"))?.contains("#include \"doctest.h\""));
+    assert!(std::fs::read_to_string(dir.join(".vscode/tasks.json"))?.contains("\"label\": \"Build tests\""));
+    assert!(std::fs::read_to_string(dir.join(".vscode/launch.json"))?.contains("\"name\": \"Debug tests\""));
+    assert!(std::fs::read_to_string(dir.join("xcpp.toml"))?.contains("test_framework = \"doctest\""));
+
+    Ok(())
+}